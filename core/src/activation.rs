@@ -1,9 +1,14 @@
+use num_traits::Float;
 use rand::distributions::{Distribution, Standard};
 use rand::Rng;
 
 #[derive(Debug, Clone, PartialEq, Hash)]
 #[cfg_attr(
-    feature = "network-serde",
+    any(
+        feature = "network-serde",
+        feature = "genome-serde",
+        feature = "config-file"
+    ),
     derive(serde::Serialize, serde::Deserialize)
 )]
 pub enum ActivationKind {
@@ -41,42 +46,51 @@ impl Distribution<ActivationKind> for Standard {
     }
 }
 
-pub fn activate(x: f64, kind: &ActivationKind) -> f64 {
+/// Converts a constant into the float type the network is calculated in
+pub(crate) fn constant<T: Float>(x: f64) -> T {
+    T::from(x).unwrap()
+}
+
+pub fn activate<T: Float>(x: T, kind: &ActivationKind) -> T {
+    let zero = T::zero();
+    let one = T::one();
+    let two = constant::<T>(2.);
+
     match kind {
         ActivationKind::Tanh => x.tanh(),
         ActivationKind::Relu => {
-            if x > 0. {
+            if x > zero {
                 x
             } else {
-                0.01 * x
+                constant::<T>(0.01) * x
             }
         }
         ActivationKind::Step => {
-            if x > 0. {
-                1.
+            if x > zero {
+                one
             } else {
-                0.
+                zero
             }
         }
-        ActivationKind::Logistic => 1. / (1. + (-x).exp()),
+        ActivationKind::Logistic => one / (one + (-x).exp()),
         ActivationKind::Identity => x,
-        ActivationKind::Softsign => x / (1. + x.abs()),
+        ActivationKind::Softsign => x / (one + x.abs()),
         ActivationKind::Sinusoid => x.sin(),
         ActivationKind::Gaussian => (-x.powi(2)).exp(),
-        ActivationKind::BentIdentity => (((x.powi(2) + 1.).sqrt() - 1.) / 2.) + x,
+        ActivationKind::BentIdentity => (((x.powi(2) + one).sqrt() - one) / two) + x,
         ActivationKind::Bipolar => {
-            if x > 0. {
-                1.
+            if x > zero {
+                one
             } else {
-                -1.
+                -one
             }
         }
-        ActivationKind::Inverse => 1. - x,
+        ActivationKind::Inverse => one - x,
         ActivationKind::SELU => {
-            let alpha = 1.6732632423543772;
-            let scale = 1.05070098735548;
+            let alpha = constant::<T>(1.6732632423543772);
+            let scale = constant::<T>(1.05070098735548);
 
-            let fx = if x > 0. { x } else { alpha * x.exp() - alpha };
+            let fx = if x > zero { x } else { alpha * x.exp() - alpha };
 
             fx * scale
         }
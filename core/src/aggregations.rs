@@ -1,25 +1,34 @@
+use num_traits::Float;
 use rand::distributions::{Distribution, Standard};
 use rand::Rng;
 
-pub fn aggregate(kind: &Aggregation, components: &[f64]) -> f64 {
+/// Components are used as scratch space, `Median` sorts them in place so nothing is allocated
+pub fn aggregate<T: Float>(kind: &Aggregation, components: &mut [T]) -> T {
     use Aggregation::*;
 
-    let func: fn(components: &[f64]) -> f64 = match kind {
-        Product => product,
-        Sum => sum,
-        Max => max,
-        Min => min,
-        MaxAbs => maxabs,
-        Median => median,
-        Mean => mean,
-    };
+    // Nodes without incoming connections only get their bias
+    if components.is_empty() {
+        return T::zero();
+    }
 
-    func(components)
+    match kind {
+        Product => product(components),
+        Sum => sum(components),
+        Max => max(components),
+        Min => min(components),
+        MaxAbs => maxabs(components),
+        Median => median(components),
+        Mean => mean(components),
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Hash)]
 #[cfg_attr(
-    feature = "network-serde",
+    any(
+        feature = "network-serde",
+        feature = "genome-serde",
+        feature = "config-file"
+    ),
     derive(serde::Serialize, serde::Deserialize)
 )]
 pub enum Aggregation {
@@ -48,52 +57,55 @@ impl Distribution<Aggregation> for Standard {
     }
 }
 
-fn product(components: &[f64]) -> f64 {
+fn product<T: Float>(components: &[T]) -> T {
     components
         .iter()
-        .fold(1., |result, current| result * current)
+        .fold(T::one(), |result, current| result * *current)
 }
 
-fn sum(components: &[f64]) -> f64 {
-    components.iter().sum()
+fn sum<T: Float>(components: &[T]) -> T {
+    components
+        .iter()
+        .fold(T::zero(), |result, current| result + *current)
 }
 
-fn max(components: &[f64]) -> f64 {
+fn max<T: Float>(components: &[T]) -> T {
     components.iter().fold(
-        f64::MIN,
+        T::min_value(),
         |max, current| if *current > max { *current } else { max },
     )
 }
 
-fn min(components: &[f64]) -> f64 {
+fn min<T: Float>(components: &[T]) -> T {
     components.iter().fold(
-        f64::MAX,
+        T::max_value(),
         |min, current| if *current < min { *current } else { min },
     )
 }
 
-fn maxabs(components: &[f64]) -> f64 {
-    let abs_components: Vec<f64> = components.iter().map(|component| component.abs()).collect();
-    max(&abs_components)
+fn maxabs<T: Float>(components: &[T]) -> T {
+    components.iter().fold(T::min_value(), |max, current| {
+        let abs = current.abs();
+        if abs > max {
+            abs
+        } else {
+            max
+        }
+    })
 }
 
-fn median(components: &[f64]) -> f64 {
-    use std::cmp::Ordering;
-
+fn median<T: Float>(components: &mut [T]) -> T {
     if components.is_empty() {
-        return 0.;
+        return T::zero();
     }
 
-    let mut sorted = components.to_vec();
-    sorted.sort_by(|a, b| {
-        if a < b {
-            Ordering::Less
-        } else {
-            Ordering::Greater
-        }
+    // NaNs are sorted last so the order stays total
+    components.sort_by(|a, b| {
+        a.partial_cmp(b)
+            .unwrap_or_else(|| a.is_nan().cmp(&b.is_nan()))
     });
 
-    let length = sorted.len();
+    let length = components.len();
     let is_length_even = length % 2 == 0;
     let median_index = if is_length_even {
         length / 2 - 1
@@ -101,12 +113,11 @@ fn median(components: &[f64]) -> f64 {
         length / 2
     };
 
-    *sorted.get(median_index).unwrap()
+    *components.get(median_index).unwrap()
 }
 
-fn mean(components: &[f64]) -> f64 {
-    let sum: f64 = components.iter().sum();
-    sum / components.len() as f64
+fn mean<T: Float>(components: &[T]) -> T {
+    sum(components) / T::from(components.len()).unwrap()
 }
 
 #[cfg(test)]
@@ -150,9 +161,9 @@ mod tests {
 
     #[test]
     fn median_works() {
-        let components = vec![3., -3., 4., -5., 1., 2.];
+        let mut components = vec![3., -3., 4., -5., 1., 2.];
 
-        assert!((median(&components) - 1.).abs() < f64::EPSILON);
+        assert!((median(&mut components) - 1.).abs() < f64::EPSILON);
     }
 
     #[test]
@@ -161,4 +172,10 @@ mod tests {
 
         assert!((mean(&components) - 2.5).abs() < f64::EPSILON);
     }
+
+    #[test]
+    fn empty_components_are_zero() {
+        assert!(aggregate::<f64>(&Aggregation::Product, &mut []).abs() < f64::EPSILON);
+        assert!(aggregate::<f64>(&Aggregation::Mean, &mut []).abs() < f64::EPSILON);
+    }
 }
@@ -1,22 +1,25 @@
+use num_traits::Float;
+
+use crate::activation::constant;
 use crate::genome::connection::ConnectionGene;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[cfg_attr(
     feature = "network-serde",
     derive(serde::Serialize, serde::Deserialize)
 )]
-pub struct Connection {
+pub struct Connection<T = f64> {
     pub from: usize,
     pub to: usize,
-    pub weight: f64,
+    pub weight: T,
 }
 
-impl From<&ConnectionGene> for Connection {
+impl<T: Float> From<&ConnectionGene> for Connection<T> {
     fn from(g: &ConnectionGene) -> Self {
         Connection {
             from: g.from,
             to: g.to,
-            weight: g.weight,
+            weight: constant(g.weight),
         }
     }
 }
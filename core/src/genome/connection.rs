@@ -1,33 +1,30 @@
-use rand::random;
+use rand::Rng;
 use std::hash::{Hash, Hasher};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "genome-serde",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub struct ConnectionGene {
     pub from: usize,
     pub to: usize,
     pub weight: f64,
     pub disabled: bool,
+    /// Historical marking, connections of different genomes with the same one are homologous
+    pub innovation: usize,
 }
 
 impl ConnectionGene {
-    pub fn new(from: usize, to: usize) -> Self {
+    pub fn new<R: Rng + ?Sized>(from: usize, to: usize, innovation: usize, rng: &mut R) -> Self {
         ConnectionGene {
             from,
             to,
-            weight: random::<f64>() * 2. - 1.,
+            weight: rng.gen::<f64>() * 2. - 1.,
             disabled: false,
+            innovation,
         }
     }
-
-    pub fn innovation_number(&self) -> usize {
-        let a = self.from;
-        let b = self.to;
-
-        let first_part = (a + b) * (a + b + 1);
-        let second_part = b;
-
-        first_part.checked_div(2).unwrap() + second_part
-    }
 }
 
 impl PartialEq for ConnectionGene {
@@ -35,6 +32,7 @@ impl PartialEq for ConnectionGene {
         self.from == other.from
             && self.to == other.to
             && self.disabled == other.disabled
+            && self.innovation == other.innovation
             && (self.weight - other.weight).abs() < f64::EPSILON
     }
 }
@@ -46,6 +44,7 @@ impl Hash for ConnectionGene {
         self.from.hash(state);
         self.to.hash(state);
         self.disabled.hash(state);
+        self.innovation.hash(state);
         self.weight.to_bits().hash(state);
     }
 }
@@ -1,8 +1,15 @@
-use rand::random;
+use rand::Rng;
+use std::collections::HashMap;
 
 use super::{ConnectionGene, Genome, NodeGene};
-
-pub fn crossover(a: (&Genome, f64), b: (&Genome, f64)) -> Option<Genome> {
+use crate::neat::Configuration;
+
+pub fn crossover<R: Rng + ?Sized>(
+    a: (&Genome, f64),
+    b: (&Genome, f64),
+    configuration: &Configuration,
+    rng: &mut R,
+) -> Option<Genome> {
     if (a.0.inputs != b.0.inputs) || (a.0.outputs != b.0.outputs) {
         return None;
     }
@@ -19,28 +26,34 @@ pub fn crossover(a: (&Genome, f64), b: (&Genome, f64)) -> Option<Genome> {
         std::mem::swap(&mut fitness_a, &mut fitness_b);
     }
 
-    let mut child = Genome::empty(parent_a.inputs, parent_a.outputs);
+    let mut child = Genome::empty(parent_a.inputs, parent_a.outputs, rng);
+
+    // Genes are matched by their historical markings, the child keeps the structure of parent A
+    let counterpart_connections: HashMap<usize, &ConnectionGene> = parent_b
+        .connection_genes
+        .iter()
+        .map(|connection| (connection.innovation, connection))
+        .collect();
+    let counterpart_nodes: HashMap<usize, &NodeGene> = parent_b
+        .node_genes
+        .iter()
+        .map(|node| (node.innovation, node))
+        .collect();
 
     let child_connection_genes: Vec<ConnectionGene> = parent_a
         .connection_genes
         .iter()
         .map(|connection| {
-            let maybe_counterpart_connection = parent_b
-                .connection_genes
-                .iter()
-                .find(|cb| cb.innovation_number() == connection.innovation_number());
+            let maybe_counterpart_connection = counterpart_connections.get(&connection.innovation);
 
-            // Chooses connection from one of the parents
-            let chosen_connection =
-                if let Some(counterpart_connection) = maybe_counterpart_connection {
-                    if random::<f64>() < 0.5 {
-                        connection
-                    } else {
-                        counterpart_connection
-                    }
-                } else {
-                    connection
-                };
+            let mut new_connection = connection.clone();
+
+            // Chooses the weight from one of the parents, the endpoints stay the ones of parent A
+            if let Some(counterpart_connection) = maybe_counterpart_connection {
+                if rng.gen::<f64>() >= 0.5 {
+                    new_connection.weight = counterpart_connection.weight;
+                }
+            }
 
             /*
              * Chooses will the new connection be disabled
@@ -48,61 +61,57 @@ pub fn crossover(a: (&Genome, f64), b: (&Genome, f64)) -> Option<Genome> {
              * - enabled in both parents, it will be enabled
              * - disabled in one parent, 50% chance it will stay disabled
              */
-            let new_disabled = if let Some(counterpart_connection) = maybe_counterpart_connection {
-                match (connection.disabled, counterpart_connection.disabled) {
-                    (true, true) => random::<f64>() < 0.75,
-                    (false, false) => false,
-                    _ => random::<f64>() < 0.5,
-                }
-            } else {
-                connection.disabled
-            };
-
-            let mut new_connection = chosen_connection.clone();
-            new_connection.disabled = new_disabled;
+            new_connection.disabled =
+                if let Some(counterpart_connection) = maybe_counterpart_connection {
+                    match (connection.disabled, counterpart_connection.disabled) {
+                        (true, true) => rng.gen::<f64>() < 0.75,
+                        (false, false) => false,
+                        _ => rng.gen::<f64>() < 0.5,
+                    }
+                } else {
+                    connection.disabled
+                };
 
             new_connection
         })
         .collect();
 
-    let required_node_count = 1 + child_connection_genes
+    let child_node_genes: Vec<NodeGene> = parent_a
+        .node_genes
         .iter()
-        .fold(0, |max, c| usize::max(usize::max(max, c.from), c.to));
-
-    let child_node_genes: Vec<NodeGene> = (0..required_node_count)
-        .map(
-            |i| match (parent_a.node_genes.get(i), parent_b.node_genes.get(i)) {
-                (Some(a), Some(b)) => {
-                    if random::<f64>() < 0.5 {
-                        a
-                    } else {
-                        b
-                    }
-                }
-                (Some(a), None) => a,
-                (None, Some(b)) => b,
-                _ => panic!("Node selection out of bounds"),
-            },
-        )
-        .cloned()
+        .map(|node| match counterpart_nodes.get(&node.innovation) {
+            Some(counterpart_node) if rng.gen::<f64>() >= 0.5 => (*counterpart_node).clone(),
+            _ => node.clone(),
+        })
         .collect();
 
     child.connection_genes = child_connection_genes;
     child.node_genes = child_node_genes;
 
-    child.node_order().and(Some(child))
+    // Feedforward children of feedforward parents can still end up with a cycle
+    if configuration.recurrent_connections {
+        Some(child)
+    } else {
+        child.node_order().and(Some(child))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::thread_rng;
 
     #[test]
     fn crossover_success() {
         let a = Genome::new(2, 2);
         let b = Genome::new(2, 2);
 
-        let maybe_child = crossover((&a, 1.), (&b, 2.));
+        let maybe_child = crossover(
+            (&a, 1.),
+            (&b, 2.),
+            &Configuration::default(),
+            &mut thread_rng(),
+        );
         assert!(maybe_child.is_some());
     }
 
@@ -111,7 +120,12 @@ mod tests {
         let a = Genome::new(2, 3);
         let b = Genome::new(2, 2);
 
-        let maybe_child = crossover((&a, 1.), (&b, 2.));
+        let maybe_child = crossover(
+            (&a, 1.),
+            (&b, 2.),
+            &Configuration::default(),
+            &mut thread_rng(),
+        );
         assert!(maybe_child.is_none());
     }
 
@@ -120,7 +134,35 @@ mod tests {
         let a = Genome::new(3, 2);
         let b = Genome::new(2, 2);
 
-        let maybe_child = crossover((&a, 1.), (&b, 2.));
+        let maybe_child = crossover(
+            (&a, 1.),
+            (&b, 2.),
+            &Configuration::default(),
+            &mut thread_rng(),
+        );
         assert!(maybe_child.is_none());
     }
+
+    #[test]
+    fn crossover_aligns_on_markings() {
+        let mut rng = thread_rng();
+
+        let mut a = Genome::new(2, 1);
+        let hidden = a.add_node(&mut rng);
+        a.add_connection(0, hidden, &mut rng).unwrap();
+
+        // Parent B gets an unrelated node in the same place
+        let mut b = a.clone();
+        b.node_genes[hidden].innovation += 1;
+        b.node_genes[hidden].bias = 5.;
+        b.connection_genes[2].innovation += 1;
+        b.connection_genes[2].weight = 5.;
+
+        for _ in 0..20 {
+            let child = crossover((&a, 2.), (&b, 1.), &Configuration::default(), &mut rng).unwrap();
+
+            assert_eq!(child.nodes()[hidden], a.nodes()[hidden]);
+            assert_eq!(child.connections()[2], a.connections()[2]);
+        }
+    }
 }
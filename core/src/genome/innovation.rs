@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use super::Genome;
+
+/// Hands out historical markings for new genes during a run
+///
+/// Structural mutations that happen more than once in the same generation, like two genomes
+/// splitting the same connection, get the same markings, so crossover and speciation can tell
+/// which genes are homologous.
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "checkpoint",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct InnovationTracker {
+    inputs: usize,
+    outputs: usize,
+    next_node: usize,
+    next_connection: usize,
+    /// Connections added this generation, by the markings of the nodes they connect
+    connections: HashMap<(usize, usize), usize>,
+    /// Nodes added this generation, by the marking of the connection they split
+    split_connections: HashMap<usize, usize>,
+}
+
+impl InnovationTracker {
+    /// Markings below the ones of the initial nodes and connections are never handed out
+    pub fn new(inputs: usize, outputs: usize) -> Self {
+        InnovationTracker {
+            inputs,
+            outputs,
+            next_node: inputs + outputs,
+            next_connection: inputs * outputs,
+            connections: HashMap::new(),
+            split_connections: HashMap::new(),
+        }
+    }
+
+    /// A tracker that continues after the markings of a single genome
+    pub fn for_genome(genome: &Genome) -> Self {
+        let mut tracker = InnovationTracker::new(genome.inputs, genome.outputs);
+        tracker.observe(genome);
+
+        tracker
+    }
+
+    /// Makes sure genes created from now on don't reuse the markings of the genome
+    pub fn observe(&mut self, genome: &Genome) {
+        if let Some(max) = genome.node_genes.iter().map(|n| n.innovation).max() {
+            self.next_node = self.next_node.max(max + 1);
+        }
+        if let Some(max) = genome.connection_genes.iter().map(|c| c.innovation).max() {
+            self.next_connection = self.next_connection.max(max + 1);
+        }
+    }
+
+    /// Forgets the mutations of the previous generation, new ones get new markings
+    pub fn new_generation(&mut self) {
+        self.connections.clear();
+        self.split_connections.clear();
+    }
+
+    /// Marking for a connection between the nodes with the given markings
+    pub fn connection(&mut self, from: usize, to: usize) -> usize {
+        if let Some(innovation) = initial_connection(self.inputs, self.outputs, from, to) {
+            return innovation;
+        }
+
+        let next_connection = &mut self.next_connection;
+
+        *self.connections.entry((from, to)).or_insert_with(|| {
+            *next_connection += 1;
+            *next_connection - 1
+        })
+    }
+
+    /// Marking for a node that splits the connection with the given marking
+    pub fn split_node(&mut self, connection: usize) -> usize {
+        let next_node = &mut self.next_node;
+
+        *self.split_connections.entry(connection).or_insert_with(|| {
+            *next_node += 1;
+            *next_node - 1
+        })
+    }
+
+    /// Marking for a node that doesn't descend from any other mutation
+    pub fn node(&mut self) -> usize {
+        self.next_node += 1;
+        self.next_node - 1
+    }
+
+    /// Gives a genome marked by another tracker markings of this one, only its input and output
+    /// nodes keep theirs
+    ///
+    /// `nodes` maps the old node markings to the new ones, sharing it between genomes of the same
+    /// origin keeps their genes homologous.
+    pub fn adopt(&mut self, genome: &mut Genome, nodes: &mut HashMap<usize, usize>) {
+        let initial_nodes = self.inputs + self.outputs;
+
+        for node in genome.node_genes.iter_mut() {
+            if node.innovation >= initial_nodes {
+                node.innovation = *nodes.entry(node.innovation).or_insert_with(|| {
+                    self.next_node += 1;
+                    self.next_node - 1
+                });
+            }
+        }
+
+        let node_genes = &genome.node_genes;
+        for connection in genome.connection_genes.iter_mut() {
+            connection.innovation = self.connection(
+                node_genes[connection.from].innovation,
+                node_genes[connection.to].innovation,
+            );
+        }
+    }
+}
+
+/// Every genome shares the markings of its input and output nodes and the connections between
+/// them, so the initial topologies line up without a tracker
+pub(crate) fn initial_connection(
+    inputs: usize,
+    outputs: usize,
+    from: usize,
+    to: usize,
+) -> Option<usize> {
+    if from < inputs && (inputs..inputs + outputs).contains(&to) {
+        Some(from * outputs + to - inputs)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    #[test]
+    fn same_mutations_share_markings() {
+        let mut tracker = InnovationTracker::new(2, 1);
+
+        assert_eq!(tracker.connection(1, 2), 1);
+
+        let node = tracker.split_node(0);
+        assert_eq!(node, 3);
+        assert_eq!(tracker.split_node(0), node);
+        assert_ne!(tracker.split_node(1), node);
+
+        let connection = tracker.connection(0, node);
+        assert_eq!(connection, 2);
+        assert_eq!(tracker.connection(0, node), connection);
+        assert_ne!(tracker.connection(node, 2), connection);
+
+        tracker.new_generation();
+        assert_ne!(tracker.split_node(0), node);
+        assert_ne!(tracker.connection(0, node), connection);
+    }
+
+    #[test]
+    fn adopted_genomes_get_new_markings() {
+        let mut rng = thread_rng();
+        let mut genome = Genome::new(2, 1);
+        let hidden = genome.add_node(&mut rng);
+        genome.add_connection(0, hidden, &mut rng).unwrap();
+        let mut other = genome.clone();
+
+        // A tracker that already handed out the same markings for other genes
+        let mut tracker = InnovationTracker::for_genome(&genome);
+        let mut nodes = HashMap::new();
+        tracker.adopt(&mut genome, &mut nodes);
+        tracker.adopt(&mut other, &mut nodes);
+
+        assert_eq!(genome.nodes()[0].innovation, 0);
+        assert_eq!(genome.connections()[0].innovation, 0);
+        assert_ne!(genome.nodes()[hidden].innovation, hidden);
+        assert_ne!(genome.connections()[2].innovation, 2);
+
+        assert_eq!(genome.nodes()[hidden], other.nodes()[hidden]);
+        assert_eq!(
+            genome.connections()[2].innovation,
+            other.connections()[2].innovation
+        );
+    }
+
+    #[test]
+    fn observed_markings_are_not_reused() {
+        let mut rng = thread_rng();
+        let mut genome = Genome::new(2, 1);
+        let hidden = genome.add_node(&mut rng);
+        genome.add_connection(0, hidden, &mut rng).unwrap();
+
+        let mut tracker = InnovationTracker::for_genome(&genome);
+        let node = tracker.node();
+        let connection = tracker.connection(1, genome.nodes()[hidden].innovation);
+
+        assert!(genome.nodes().iter().all(|n| n.innovation != node));
+        assert!(genome
+            .connections()
+            .iter()
+            .all(|c| c.innovation != connection));
+    }
+}
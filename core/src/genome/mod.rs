@@ -1,19 +1,44 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use rand::{thread_rng, Rng};
+use std::collections::{HashSet, VecDeque};
 use uuid::Uuid;
 
 use crate::mutations::MutationKind;
+use crate::neat::Configuration;
 use crate::node::NodeKind;
 pub use connection::ConnectionGene;
 pub use crossover::*;
+pub use innovation::InnovationTracker;
 pub use node::NodeGene;
 
 pub mod connection;
 pub mod crossover;
+pub mod innovation;
 pub mod node;
 
 pub type GenomeId = Uuid;
 
+/// How the inputs are connected to the outputs in the initial population
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    any(feature = "checkpoint", feature = "config-file"),
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum InitialTopology {
+    /// Every input is connected to every output
+    Full,
+    /// There are no connections, evolution has to add all of them
+    None,
+    /// Every output is connected to a single random input, as in FS-NEAT
+    FsNeat,
+    /// Every output is connected to this fraction of random inputs
+    Fraction(f64),
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "genome-serde",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub struct Genome {
     id: Uuid,
     inputs: usize,
@@ -22,23 +47,72 @@ pub struct Genome {
     node_genes: Vec<NodeGene>,
 }
 
+/// Generates a random (version 4) genome id from the given generator
+fn random_id<R: Rng + ?Sized>(rng: &mut R) -> GenomeId {
+    uuid::Builder::from_bytes(rng.gen())
+        .set_variant(uuid::Variant::RFC4122)
+        .set_version(uuid::Version::Random)
+        .build()
+}
+
 impl Genome {
     pub fn new(inputs: usize, outputs: usize) -> Self {
+        Genome::new_with_rng(inputs, outputs, &mut thread_rng())
+    }
+
+    /// Creates a fully connected genome, taking all randomness from the given generator
+    pub fn new_with_rng<R: Rng + ?Sized>(inputs: usize, outputs: usize, rng: &mut R) -> Self {
+        Genome::with_topology(inputs, outputs, &InitialTopology::Full, rng)
+    }
+
+    /// Creates a genome whose inputs are connected to the outputs as the topology says
+    pub fn with_topology<R: Rng + ?Sized>(
+        inputs: usize,
+        outputs: usize,
+        topology: &InitialTopology,
+        rng: &mut R,
+    ) -> Self {
         let mut node_genes = vec![];
 
-        (0..inputs).for_each(|_| node_genes.push(NodeGene::new(NodeKind::Input)));
-        (0..outputs).for_each(|_| node_genes.push(NodeGene::new(NodeKind::Output)));
+        // Input and output nodes are marked by their index, the same in every genome
+        (0..inputs).for_each(|i| node_genes.push(NodeGene::new(NodeKind::Input, i, rng)));
+        (inputs..inputs + outputs)
+            .for_each(|o| node_genes.push(NodeGene::new(NodeKind::Output, o, rng)));
+
+        let connected_pairs: Vec<(usize, usize)> = match topology {
+            InitialTopology::Full => (0..inputs)
+                .flat_map(|i| (inputs..inputs + outputs).map(move |o| (i, o)))
+                .collect(),
+            InitialTopology::None => vec![],
+            InitialTopology::FsNeat if inputs == 0 => vec![],
+            InitialTopology::FsNeat => (inputs..inputs + outputs)
+                .map(|o| (rng.gen::<usize>() % inputs, o))
+                .collect(),
+            InitialTopology::Fraction(fraction) => {
+                let amount = ((inputs as f64 * fraction).round() as usize).min(inputs);
 
-        let connection_genes: Vec<ConnectionGene> = (0..inputs)
-            .flat_map(|i| {
                 (inputs..inputs + outputs)
-                    .map(|o| ConnectionGene::new(i, o))
-                    .collect::<Vec<ConnectionGene>>()
+                    .flat_map(|o| {
+                        let mut picked = rand::seq::index::sample(rng, inputs, amount).into_vec();
+                        picked.sort_unstable();
+
+                        picked.into_iter().map(move |i| (i, o))
+                    })
+                    .collect()
+            }
+        };
+
+        let connection_genes: Vec<ConnectionGene> = connected_pairs
+            .into_iter()
+            .map(|(i, o)| {
+                let innovation = innovation::initial_connection(inputs, outputs, i, o).unwrap();
+
+                ConnectionGene::new(i, o, innovation, rng)
             })
             .collect();
 
         Genome {
-            id: Uuid::new_v4(),
+            id: random_id(rng),
             inputs,
             outputs,
             connection_genes,
@@ -46,9 +120,9 @@ impl Genome {
         }
     }
 
-    fn empty(inputs: usize, outputs: usize) -> Self {
+    fn empty<R: Rng + ?Sized>(inputs: usize, outputs: usize, rng: &mut R) -> Self {
         Genome {
-            id: Uuid::new_v4(),
+            id: random_id(rng),
             inputs,
             outputs,
             connection_genes: vec![],
@@ -56,6 +130,30 @@ impl Genome {
         }
     }
 
+    /// Assembles a genome from existing genes, for phenotypes that aren't part of a population
+    pub(crate) fn from_genes(
+        inputs: usize,
+        outputs: usize,
+        node_genes: Vec<NodeGene>,
+        connection_genes: Vec<ConnectionGene>,
+    ) -> Self {
+        Genome {
+            id: Uuid::nil(),
+            inputs,
+            outputs,
+            connection_genes,
+            node_genes,
+        }
+    }
+
+    /// Copies the genome under a new id so both can be part of the same population
+    pub(crate) fn copy_with_new_id<R: Rng + ?Sized>(&self, rng: &mut R) -> Self {
+        Genome {
+            id: random_id(rng),
+            ..self.clone()
+        }
+    }
+
     pub fn id(&self) -> GenomeId {
         // use std::collections::hash_map::DefaultHasher;
         // use std::hash::{Hash, Hasher};
@@ -106,10 +204,6 @@ impl Genome {
             connections.append(&mut conns);
         }
 
-        if connections.is_empty() {
-            return None;
-        }
-
         let mut visited: Vec<usize> = vec![];
 
         // Input nodes are automatically visited as they get their values from inputs
@@ -163,54 +257,36 @@ impl Genome {
         self.calculate_node_order(Some(additional_connections))
     }
 
-    fn calculate_node_distance_from_inputs(&self) -> HashMap<usize, usize> {
-        // Inputs are immediately added with distance of 0
-        let mut distances: HashMap<usize, usize> = self
-            .nodes()
-            .iter()
-            .enumerate()
-            .filter(|(_, n)| matches!(n.kind, NodeKind::Input))
-            .map(|(i, _)| (i, 0))
-            .collect();
-
-        // Inputs need to be visited first
-        let mut to_visit: VecDeque<usize> = self
-            .nodes()
-            .iter()
-            .enumerate()
-            .filter(|(_, n)| matches!(n.kind, NodeKind::Input))
-            .map(|(i, _)| i)
-            .collect();
-
-        while let Some(i) = to_visit.pop_front() {
-            let source_distance = *distances.get(&i).unwrap_or(&0);
+    /// Orders all nodes so they come after the nodes they depend on, as far as cycles allow
+    pub fn recurrent_node_order(&self) -> Vec<usize> {
+        fn visit(
+            index: usize,
+            incoming: &[Vec<usize>],
+            visited: &mut Vec<bool>,
+            order: &mut Vec<usize>,
+        ) {
+            if visited[index] {
+                return;
+            }
 
-            self.connections()
+            visited[index] = true;
+            incoming[index]
                 .iter()
-                .filter(|c| c.from == i)
-                .for_each(|c| {
-                    let node_index = c.to;
-                    let potential_distance = source_distance + 1;
-
-                    let maybe_change = if let Some(distance) = distances.get(&node_index) {
-                        if potential_distance > *distance {
-                            to_visit.push_back(node_index);
-                            Some(potential_distance)
-                        } else {
-                            None
-                        }
-                    } else {
-                        to_visit.push_back(node_index);
-                        Some(potential_distance)
-                    };
-
-                    if let Some(new_distance) = maybe_change {
-                        distances.insert(node_index, new_distance);
-                    }
-                });
+                .for_each(|from| visit(*from, incoming, visited, order));
+            order.push(index);
         }
 
-        distances
+        let mut incoming: Vec<Vec<usize>> = vec![vec![]; self.node_genes.len()];
+        self.connection_genes
+            .iter()
+            .filter(|c| !c.disabled)
+            .for_each(|c| incoming[c.to].push(c.from));
+
+        let mut visited = vec![false; self.node_genes.len()];
+        let mut order = Vec::with_capacity(self.node_genes.len());
+        (0..self.node_genes.len()).for_each(|i| visit(i, &incoming, &mut visited, &mut order));
+
+        order
     }
 
     fn is_projecting_directly(&self, source: usize, target: usize) -> bool {
@@ -239,7 +315,7 @@ impl Genome {
             } else {
                 self.connection_genes
                     .iter()
-                    .filter(|c| c.from == i && !c.disabled && !visited_nodes.contains(&i))
+                    .filter(|c| c.from == i && !c.disabled && !visited_nodes.contains(&c.to))
                     .for_each(|c| nodes_to_visit.push_back(c.to));
             }
         }
@@ -258,23 +334,63 @@ impl Genome {
         let is_from_output = matches!(from_node.kind, NodeKind::Output);
         let is_to_input = matches!(to_node.kind, NodeKind::Input);
 
-        let distances = self.calculate_node_distance_from_inputs();
-        let from_distance = distances.get(&from).unwrap();
-        let to_distance = distances.get(&to).unwrap_or(&usize::MAX);
-        let is_recurrent = from_distance > to_distance;
+        // The connection would close a cycle if `to` already leads to `from`
+        let is_recurrent = from == to || self.is_projecting(to, from);
 
         if is_from_output || is_to_input || is_recurrent {
             false
         } else {
-            !self.is_projecting(from, to)
+            !self.is_projecting_directly(from, to)
         }
     }
 
-    pub fn add_connection(&mut self, from: usize, to: usize) -> Result<usize, ()> {
+    /// Like `can_connect`, but also allows cycles and self connections
+    pub fn can_connect_recurrent(&self, from: usize, to: usize) -> bool {
+        let to_node = self.node_genes.get(to).unwrap();
+
+        !matches!(to_node.kind, NodeKind::Input) && !self.is_projecting_directly(from, to)
+    }
+
+    /// Connections added directly get markings that are only unique within this genome, evolution
+    /// uses an `InnovationTracker` shared by the population instead
+    pub fn add_connection<R: Rng + ?Sized>(
+        &mut self,
+        from: usize,
+        to: usize,
+        rng: &mut R,
+    ) -> Result<usize, ()> {
         if !self.can_connect(from, to) {
             return Err(());
         }
 
+        let mut innovations = InnovationTracker::for_genome(self);
+        Ok(self.insert_connection(from, to, &mut innovations, rng))
+    }
+
+    /// Adds a connection that may form a cycle or connect a node to itself, returns its index or
+    /// `None` if the nodes can't be connected
+    pub fn add_recurrent_connection<R: Rng + ?Sized>(
+        &mut self,
+        from: usize,
+        to: usize,
+        rng: &mut R,
+    ) -> Option<usize> {
+        if !self.can_connect_recurrent(from, to) {
+            return None;
+        }
+
+        let mut innovations = InnovationTracker::for_genome(self);
+        Some(self.insert_connection(from, to, &mut innovations, rng))
+    }
+
+    /// Enables an existing connection between the nodes or adds a new one, without any checks
+    pub(crate) fn insert_connection<R: Rng + ?Sized>(
+        &mut self,
+        from: usize,
+        to: usize,
+        innovations: &mut InnovationTracker,
+        rng: &mut R,
+    ) -> usize {
         let maybe_connection = self
             .connection_genes
             .iter_mut()
@@ -283,16 +399,26 @@ impl Genome {
         if let Some(mut conn) = maybe_connection {
             conn.disabled = false;
         } else {
-            self.connection_genes.push(ConnectionGene::new(from, to));
+            let innovation = innovations.connection(
+                self.node_genes[from].innovation,
+                self.node_genes[to].innovation,
+            );
+
+            self.connection_genes
+                .push(ConnectionGene::new(from, to, innovation, rng));
         }
 
-        Ok(self.connection_genes.len() - 1)
+        self.connection_genes.len() - 1
     }
 
-    pub fn add_many_connections(&mut self, params: &[(usize, usize)]) -> Vec<Result<usize, ()>> {
+    pub fn add_many_connections<R: Rng + ?Sized>(
+        &mut self,
+        params: &[(usize, usize)],
+        rng: &mut R,
+    ) -> Vec<Result<usize, ()>> {
         let results = params
             .iter()
-            .map(|(from, to)| self.add_connection(*from, *to))
+            .map(|(from, to)| self.add_connection(*from, *to, rng))
             .collect();
 
         results
@@ -306,16 +432,30 @@ impl Genome {
         indexes.iter().for_each(|i| self.disable_connection(*i));
     }
 
-    /// Add a new hidden node to the genome
-    pub fn add_node(&mut self) -> usize {
+    /// Add a new hidden node to the genome, marked like connections added with `add_connection`
+    pub fn add_node<R: Rng + ?Sized>(&mut self, rng: &mut R) -> usize {
+        let innovation = InnovationTracker::for_genome(self).node();
+
+        self.insert_node(innovation, rng)
+    }
+
+    pub(crate) fn insert_node<R: Rng + ?Sized>(&mut self, innovation: usize, rng: &mut R) -> usize {
         let index = self.node_genes.len();
-        self.node_genes.push(NodeGene::new(NodeKind::Hidden));
+        self.node_genes
+            .push(NodeGene::new(NodeKind::Hidden, innovation, rng));
 
         index
     }
 
-    pub fn mutate(&mut self, kind: &MutationKind) {
-        crate::mutations::mutate(kind, self);
+    /// Structural mutations take the markings of their new genes from `innovations`
+    pub fn mutate<R: Rng + ?Sized>(
+        &mut self,
+        kind: &MutationKind,
+        configuration: &Configuration,
+        innovations: &mut InnovationTracker,
+        rng: &mut R,
+    ) {
+        crate::mutations::mutate(kind, self, configuration, innovations, rng);
     }
 }
 
@@ -328,11 +468,32 @@ mod tests {
         Genome::new(2, 2);
     }
 
+    #[test]
+    fn initial_topologies() {
+        let mut rng = thread_rng();
+
+        let full = Genome::with_topology(10, 2, &InitialTopology::Full, &mut rng);
+        assert_eq!(full.connections().len(), 20);
+
+        let none = Genome::with_topology(10, 2, &InitialTopology::None, &mut rng);
+        assert_eq!(none.nodes().len(), 12);
+        assert!(none.connections().is_empty());
+        assert_eq!(none.node_order().unwrap().len(), 12);
+
+        let fs_neat = Genome::with_topology(10, 2, &InitialTopology::FsNeat, &mut rng);
+        assert_eq!(fs_neat.connections().len(), 2);
+        assert!(fs_neat.connections().iter().any(|c| c.to == 10));
+        assert!(fs_neat.connections().iter().any(|c| c.to == 11));
+
+        let fraction = Genome::with_topology(10, 2, &InitialTopology::Fraction(0.3), &mut rng);
+        assert_eq!(fraction.connections().len(), 6);
+    }
+
     #[test]
     fn add_node_does_not_change_connections() {
         let mut g = Genome::new(1, 2);
 
-        g.add_node();
+        g.add_node(&mut thread_rng());
 
         let first_connection = g.connection_genes.get(0).unwrap();
         assert_eq!(first_connection.from, 0);
@@ -373,67 +534,92 @@ mod tests {
         assert!(!g.is_projected_directly(1, 3));
     }
 
-    // TODO rewrite the tests or both the implementation and tests
-
-    // #[test]
-    // fn is_projecting() {
-    //     let mut g = Genome::empty(1, 1);
-
-    //     g.node_genes.push(NodeGene::new(NodeKind::Input));
-    //     g.node_genes.push(NodeGene::new(NodeKind::Hidden));
-    //     g.node_genes.push(NodeGene::new(NodeKind::Hidden));
-    //     g.node_genes.push(NodeGene::new(NodeKind::Output));
-
-    //     g.connection_genes.push(ConnectionGene::new(0, 1));
-    //     g.connection_genes.push(ConnectionGene::new(1, 2));
-    //     g.connection_genes.push(ConnectionGene::new(2, 3));
-
-    //     assert!(g.is_projecting(0, 3));
-    //     assert!(g.is_projecting(1, 3));
-    //     assert!(g.is_projecting(2, 3));
-
-    //     assert!(!g.is_projecting(3, 0));
-    //     assert!(!g.is_projecting(3, 1));
-    //     assert!(!g.is_projecting(3, 2));
-    // }
-
-    // #[test]
-    // fn is_projected() {
-    //     let mut g = Genome::empty(1, 1);
-
-    //     g.node_genes.push(NodeGene::new(NodeKind::Input));
-    //     g.node_genes.push(NodeGene::new(NodeKind::Hidden));
-    //     g.node_genes.push(NodeGene::new(NodeKind::Hidden));
-    //     g.node_genes.push(NodeGene::new(NodeKind::Output));
-
-    //     g.connection_genes.push(ConnectionGene::new(0, 1));
-    //     g.connection_genes.push(ConnectionGene::new(1, 2));
-    //     g.connection_genes.push(ConnectionGene::new(2, 3));
-
-    //     assert!(g.is_projected(3, 0));
-    //     assert!(g.is_projected(3, 1));
-    //     assert!(g.is_projected(3, 2));
+    #[test]
+    fn is_projecting() {
+        let mut rng = thread_rng();
+        let mut g = Genome::empty(1, 1, &mut rng);
+
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Input, 0, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Hidden, 1, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Hidden, 2, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Output, 3, &mut rng));
+
+        g.connection_genes
+            .push(ConnectionGene::new(0, 1, 0, &mut rng));
+        g.connection_genes
+            .push(ConnectionGene::new(1, 2, 1, &mut rng));
+        g.connection_genes
+            .push(ConnectionGene::new(2, 3, 2, &mut rng));
+
+        assert!(g.is_projecting(0, 3));
+        assert!(g.is_projecting(1, 3));
+        assert!(g.is_projecting(2, 3));
+
+        assert!(!g.is_projecting(3, 0));
+        assert!(!g.is_projecting(3, 1));
+        assert!(!g.is_projecting(3, 2));
+    }
 
-    //     assert!(!g.is_projected(0, 3));
-    //     assert!(!g.is_projected(1, 3));
-    //     assert!(!g.is_projected(2, 3));
-    // }
+    #[test]
+    fn is_projected() {
+        let mut rng = thread_rng();
+        let mut g = Genome::empty(1, 1, &mut rng);
+
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Input, 0, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Hidden, 1, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Hidden, 2, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Output, 3, &mut rng));
+
+        g.connection_genes
+            .push(ConnectionGene::new(0, 1, 0, &mut rng));
+        g.connection_genes
+            .push(ConnectionGene::new(1, 2, 1, &mut rng));
+        g.connection_genes
+            .push(ConnectionGene::new(2, 3, 2, &mut rng));
+
+        assert!(g.is_projected(3, 0));
+        assert!(g.is_projected(3, 1));
+        assert!(g.is_projected(3, 2));
+
+        assert!(!g.is_projected(0, 3));
+        assert!(!g.is_projected(1, 3));
+        assert!(!g.is_projected(2, 3));
+    }
 
     #[test]
     fn can_connect() {
-        let mut g = Genome::empty(1, 1);
-
-        g.node_genes.push(NodeGene::new(NodeKind::Input));
-        g.node_genes.push(NodeGene::new(NodeKind::Hidden));
-        g.node_genes.push(NodeGene::new(NodeKind::Hidden));
-        g.node_genes.push(NodeGene::new(NodeKind::Hidden));
-        g.node_genes.push(NodeGene::new(NodeKind::Output));
-
-        g.connection_genes.push(ConnectionGene::new(0, 1));
-        g.connection_genes.push(ConnectionGene::new(0, 2));
-        g.connection_genes.push(ConnectionGene::new(1, 3));
-        g.connection_genes.push(ConnectionGene::new(2, 3));
-        g.connection_genes.push(ConnectionGene::new(3, 4));
+        let mut rng = thread_rng();
+        let mut g = Genome::empty(1, 1, &mut rng);
+
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Input, 0, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Hidden, 1, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Hidden, 2, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Hidden, 3, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Output, 4, &mut rng));
+
+        g.connection_genes
+            .push(ConnectionGene::new(0, 1, 0, &mut rng));
+        g.connection_genes
+            .push(ConnectionGene::new(0, 2, 1, &mut rng));
+        g.connection_genes
+            .push(ConnectionGene::new(1, 3, 2, &mut rng));
+        g.connection_genes
+            .push(ConnectionGene::new(2, 3, 3, &mut rng));
+        g.connection_genes
+            .push(ConnectionGene::new(3, 4, 4, &mut rng));
 
         assert!(g.can_connect(1, 2));
         assert!(g.can_connect(2, 1));
@@ -444,76 +630,124 @@ mod tests {
         assert!(!g.can_connect(4, 2));
     }
 
+    #[test]
+    fn can_connect_unreachable_nodes() {
+        let mut rng = thread_rng();
+        let mut g = Genome::new(1, 1);
+
+        // Neither hidden node can be reached from the input
+        let first = g.add_node(&mut rng);
+        let second = g.add_node(&mut rng);
+        g.add_connection(first, second, &mut rng).unwrap();
+
+        assert!(!g.can_connect(second, first));
+        assert!(!g.can_connect(first, first));
+        assert!(g.add_connection(second, first, &mut rng).is_err());
+    }
+
     #[test]
     fn get_node_order() {
-        let mut g = Genome::empty(2, 1);
-
-        g.node_genes.push(NodeGene::new(NodeKind::Input));
-        g.node_genes.push(NodeGene::new(NodeKind::Input));
-        g.node_genes.push(NodeGene::new(NodeKind::Output));
-        g.node_genes.push(NodeGene::new(NodeKind::Hidden));
-        g.node_genes.push(NodeGene::new(NodeKind::Hidden));
-        g.node_genes.push(NodeGene::new(NodeKind::Hidden));
-
-        g.add_connection(0, 2).unwrap();
-        g.add_connection(1, 3).unwrap();
-        g.add_connection(1, 4).unwrap();
-        g.add_connection(1, 5).unwrap();
-        g.add_connection(3, 2).unwrap();
-        g.add_connection(4, 3).unwrap();
-        g.add_connection(5, 4).unwrap();
+        let mut rng = thread_rng();
+        let mut g = Genome::empty(2, 1, &mut rng);
+
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Input, 0, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Input, 1, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Output, 2, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Hidden, 3, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Hidden, 4, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Hidden, 5, &mut rng));
+
+        g.add_connection(0, 2, &mut rng).unwrap();
+        g.add_connection(1, 3, &mut rng).unwrap();
+        g.add_connection(1, 4, &mut rng).unwrap();
+        g.add_connection(1, 5, &mut rng).unwrap();
+        g.add_connection(3, 2, &mut rng).unwrap();
+        g.add_connection(4, 3, &mut rng).unwrap();
+        g.add_connection(5, 4, &mut rng).unwrap();
 
         assert!(g.node_order().is_some());
-        assert!(g.node_order_with(vec![ConnectionGene::new(3, 5)]).is_none());
+        assert!(g
+            .node_order_with(vec![ConnectionGene::new(3, 5, 0, &mut rng)])
+            .is_none());
     }
 
     #[test]
     fn no_recurrent_connections() {
-        let mut g = Genome::empty(2, 1);
-
-        g.node_genes.push(NodeGene::new(NodeKind::Input));
-        g.node_genes.push(NodeGene::new(NodeKind::Input));
-        g.node_genes.push(NodeGene::new(NodeKind::Output));
-        g.node_genes.push(NodeGene::new(NodeKind::Hidden));
-        g.node_genes.push(NodeGene::new(NodeKind::Hidden));
-        g.node_genes.push(NodeGene::new(NodeKind::Hidden));
-
-        g.add_connection(0, 2).unwrap();
-        g.add_connection(1, 3).unwrap();
-        g.add_connection(1, 4).unwrap();
-        g.add_connection(1, 5).unwrap();
-        g.add_connection(3, 2).unwrap();
-        g.add_connection(4, 3).unwrap();
-        g.add_connection(5, 4).unwrap();
-
-        assert!(g.add_connection(3, 5).is_err());
+        let mut rng = thread_rng();
+        let mut g = Genome::empty(2, 1, &mut rng);
+
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Input, 0, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Input, 1, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Output, 2, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Hidden, 3, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Hidden, 4, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Hidden, 5, &mut rng));
+
+        g.add_connection(0, 2, &mut rng).unwrap();
+        g.add_connection(1, 3, &mut rng).unwrap();
+        g.add_connection(1, 4, &mut rng).unwrap();
+        g.add_connection(1, 5, &mut rng).unwrap();
+        g.add_connection(3, 2, &mut rng).unwrap();
+        g.add_connection(4, 3, &mut rng).unwrap();
+        g.add_connection(5, 4, &mut rng).unwrap();
+
+        assert!(g.add_connection(3, 5, &mut rng).is_err());
     }
 
     #[test]
-    fn node_distances_simple() {
-        let g = Genome::new(2, 1);
+    fn recurrent_connections_when_allowed() {
+        let mut rng = thread_rng();
+        let mut g = Genome::new(1, 1);
+        g.add_node(&mut rng);
+
+        g.add_connection(0, 2, &mut rng).unwrap();
+        g.add_connection(2, 1, &mut rng).unwrap();
+
+        assert!(g.add_recurrent_connection(1, 2, &mut rng).is_some());
+        assert!(g.add_recurrent_connection(2, 2, &mut rng).is_some());
+        assert!(g.add_recurrent_connection(1, 0, &mut rng).is_none());
 
-        dbg!(g.calculate_node_distance_from_inputs());
+        assert!(g.node_order().is_none());
+        assert_eq!(g.recurrent_node_order(), vec![0, 2, 1]);
     }
 
     #[test]
     fn node_distances_block_recurrent_connections() {
-        let mut g = Genome::empty(2, 1);
-
-        g.node_genes.push(NodeGene::new(NodeKind::Input));
-        g.node_genes.push(NodeGene::new(NodeKind::Input));
-        g.node_genes.push(NodeGene::new(NodeKind::Output));
-        g.node_genes.push(NodeGene::new(NodeKind::Hidden));
-        g.node_genes.push(NodeGene::new(NodeKind::Hidden));
-        g.node_genes.push(NodeGene::new(NodeKind::Hidden));
-
-        g.add_connection(0, 3).unwrap();
-        g.add_connection(1, 3).unwrap();
-        g.add_connection(3, 4).unwrap();
-        g.add_connection(4, 5).unwrap();
-        g.add_connection(4, 2).unwrap();
-        g.add_connection(5, 2).unwrap();
-
-        assert!(g.add_connection(5, 3).is_err());
+        let mut rng = thread_rng();
+        let mut g = Genome::empty(2, 1, &mut rng);
+
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Input, 0, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Input, 1, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Output, 2, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Hidden, 3, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Hidden, 4, &mut rng));
+        g.node_genes
+            .push(NodeGene::new(NodeKind::Hidden, 5, &mut rng));
+
+        g.add_connection(0, 3, &mut rng).unwrap();
+        g.add_connection(1, 3, &mut rng).unwrap();
+        g.add_connection(3, 4, &mut rng).unwrap();
+        g.add_connection(4, 5, &mut rng).unwrap();
+        g.add_connection(4, 2, &mut rng).unwrap();
+        g.add_connection(5, 2, &mut rng).unwrap();
+
+        assert!(g.add_connection(5, 3, &mut rng).is_err());
     }
 }
@@ -1,27 +1,35 @@
 use crate::activation::ActivationKind;
 use crate::aggregations::Aggregation;
 use crate::node::NodeKind;
-use rand::random;
+use rand::Rng;
 use std::hash::{Hash, Hasher};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "genome-serde",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub struct NodeGene {
     pub kind: NodeKind,
     pub aggregation: Aggregation,
     pub activation: ActivationKind,
     pub bias: f64,
+    /// How slowly the node reacts when the network is advanced in continuous time
+    pub time_constant: f64,
+    /// Historical marking, nodes of different genomes with the same one are homologous
+    pub innovation: usize,
 }
 
 impl NodeGene {
-    pub fn new(kind: NodeKind) -> Self {
-        let aggregation = random();
+    pub fn new<R: Rng + ?Sized>(kind: NodeKind, innovation: usize, rng: &mut R) -> Self {
+        let aggregation = rng.gen();
         let activation = match kind {
             NodeKind::Input => ActivationKind::Input,
-            _ => random(),
+            _ => rng.gen(),
         };
         let bias: f64 = match kind {
             NodeKind::Input => 0.,
-            _ => random::<f64>() * 2. - 1.,
+            _ => rng.gen::<f64>() * 2. - 1.,
         };
 
         NodeGene {
@@ -29,6 +37,8 @@ impl NodeGene {
             kind,
             activation,
             bias,
+            time_constant: 1.,
+            innovation,
         }
     }
 }
@@ -36,9 +46,11 @@ impl NodeGene {
 impl PartialEq for NodeGene {
     fn eq(&self, other: &Self) -> bool {
         self.kind == other.kind
+            && self.innovation == other.innovation
             && self.aggregation == other.aggregation
             && self.activation == other.activation
             && (self.bias - other.bias).abs() < f64::EPSILON
+            && (self.time_constant - other.time_constant).abs() < f64::EPSILON
     }
 }
 
@@ -47,8 +59,10 @@ impl Eq for NodeGene {}
 impl Hash for NodeGene {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.kind.hash(state);
+        self.innovation.hash(state);
         self.aggregation.hash(state);
         self.activation.hash(state);
         self.bias.to_bits().hash(state);
+        self.time_constant.to_bits().hash(state);
     }
 }
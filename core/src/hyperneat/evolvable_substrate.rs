@@ -0,0 +1,343 @@
+use std::collections::HashSet;
+
+use super::{phenotype, query_weight, Point};
+use crate::activation::ActivationKind;
+use crate::network::Network;
+
+/// A substrate where only inputs and outputs are placed, ES-HyperNEAT finds the hidden nodes
+/// where the CPPN shows the most information
+#[derive(Debug, Clone)]
+pub struct EvolvableSubstrate {
+    pub inputs: Vec<Point>,
+    pub outputs: Vec<Point>,
+
+    /// The quadtree is always divided at least this deep
+    pub initial_depth: usize,
+
+    /// The quadtree is never divided deeper than this
+    pub max_depth: usize,
+
+    /// Quadtree nodes with a higher weight variance get divided further
+    pub division_threshold: f64,
+
+    /// Quadtree nodes with a lower weight variance are candidates for connections
+    pub variance_threshold: f64,
+
+    /// How much a candidate has to differ from its neighbours to become a connection
+    pub band_threshold: f64,
+
+    /// How many times hidden nodes search for further hidden nodes
+    pub iteration_level: usize,
+
+    /// Magnitude of the weight for the strongest CPPN output
+    pub max_weight: f64,
+
+    pub hidden_activation: ActivationKind,
+    pub output_activation: ActivationKind,
+}
+
+/// A square of the quadtree and the CPPN weight at its center
+struct QuadPoint {
+    x: f64,
+    y: f64,
+    width: f64,
+    level: usize,
+    weight: f64,
+    children: Vec<QuadPoint>,
+}
+
+impl QuadPoint {
+    fn leaf_weights(&self, weights: &mut Vec<f64>) {
+        if self.children.is_empty() {
+            weights.push(self.weight);
+        } else {
+            self.children.iter().for_each(|c| c.leaf_weights(weights));
+        }
+    }
+
+    fn variance(&self) -> f64 {
+        if self.children.is_empty() {
+            return 0.;
+        }
+
+        let mut weights = vec![];
+        self.leaf_weights(&mut weights);
+
+        let mean = weights.iter().sum::<f64>() / weights.len() as f64;
+
+        weights.iter().map(|w| (w - mean).powi(2)).sum::<f64>() / weights.len() as f64
+    }
+}
+
+impl EvolvableSubstrate {
+    pub fn new(inputs: Vec<Point>, outputs: Vec<Point>) -> Self {
+        EvolvableSubstrate {
+            inputs,
+            outputs,
+            initial_depth: 3,
+            max_depth: 5,
+            division_threshold: 0.03,
+            variance_threshold: 0.03,
+            band_threshold: 0.3,
+            iteration_level: 1,
+            max_weight: 5.,
+            hidden_activation: ActivationKind::Tanh,
+            output_activation: ActivationKind::Tanh,
+        }
+    }
+
+    fn query(&self, cppn: &mut Network, source: Point, point: Point, outgoing: bool) -> f64 {
+        if outgoing {
+            query_weight(cppn, source, point)
+        } else {
+            query_weight(cppn, point, source)
+        }
+    }
+
+    /// Builds the quadtree of connection weights from or to `source`
+    fn divide(&self, cppn: &mut Network, source: Point, outgoing: bool, p: &mut QuadPoint) {
+        let half_width = p.width / 2.;
+
+        p.children = [(-1., -1.), (-1., 1.), (1., -1.), (1., 1.)]
+            .iter()
+            .map(|(dx, dy)| {
+                let (x, y) = (p.x + dx * half_width, p.y + dy * half_width);
+
+                QuadPoint {
+                    x,
+                    y,
+                    width: half_width,
+                    level: p.level + 1,
+                    weight: self.query(cppn, source, (x, y), outgoing),
+                    children: vec![],
+                }
+            })
+            .collect();
+
+        if p.level < self.initial_depth
+            || (p.level < self.max_depth && p.variance() > self.division_threshold)
+        {
+            for child in p.children.iter_mut() {
+                self.divide(cppn, source, outgoing, child);
+            }
+        }
+    }
+
+    /// Picks the points of uniform areas that stand out from their neighbours
+    fn prune_and_extract(
+        &self,
+        cppn: &mut Network,
+        source: Point,
+        outgoing: bool,
+        p: &QuadPoint,
+        connections: &mut Vec<(Point, Point, f64)>,
+    ) {
+        for c in &p.children {
+            if c.variance() >= self.variance_threshold {
+                self.prune_and_extract(cppn, source, outgoing, c, connections);
+                continue;
+            }
+
+            let mut difference =
+                |x: f64, y: f64| (c.weight - self.query(cppn, source, (x, y), outgoing)).abs();
+
+            let left = difference(c.x - p.width, c.y);
+            let right = difference(c.x + p.width, c.y);
+            let top = difference(c.x, c.y + p.width);
+            let bottom = difference(c.x, c.y - p.width);
+
+            let band = f64::max(f64::min(top, bottom), f64::min(left, right));
+
+            if band > self.band_threshold {
+                let weight = c.weight * self.max_weight;
+
+                if outgoing {
+                    connections.push((source, (c.x, c.y), weight));
+                } else {
+                    connections.push(((c.x, c.y), source, weight));
+                }
+            }
+        }
+    }
+
+    /// All connections worth expressing from or to the given point
+    fn discover(
+        &self,
+        cppn: &mut Network,
+        source: Point,
+        outgoing: bool,
+    ) -> Vec<(Point, Point, f64)> {
+        let mut root = QuadPoint {
+            x: 0.,
+            y: 0.,
+            width: 1.,
+            level: 1,
+            weight: 0.,
+            children: vec![],
+        };
+        self.divide(cppn, source, outgoing, &mut root);
+
+        let mut connections = vec![];
+        self.prune_and_extract(cppn, source, outgoing, &root, &mut connections);
+
+        connections
+    }
+
+    /// Discovers the hidden nodes and connections, nodes that can't affect the outputs are dropped
+    pub fn build_network(&self, cppn: &mut Network) -> Network {
+        let mut hidden: Vec<Point> = vec![];
+        let mut connections: Vec<(Point, Point, f64)> = vec![];
+
+        // Inputs to hidden nodes, then hidden nodes to further hidden nodes
+        let mut unexplored: Vec<Point> = self.inputs.clone();
+        for _ in 0..=self.iteration_level {
+            let mut newly_found = vec![];
+
+            for source in &unexplored {
+                for (from, to, weight) in self.discover(cppn, *source, true) {
+                    if !hidden.contains(&to) {
+                        hidden.push(to);
+                        newly_found.push(to);
+                    }
+
+                    connections.push((from, to, weight));
+                }
+            }
+
+            unexplored = newly_found;
+        }
+
+        // Hidden nodes to outputs
+        for output in &self.outputs {
+            self.discover(cppn, *output, false)
+                .into_iter()
+                .filter(|(from, _, _)| hidden.contains(from))
+                .for_each(|connection| connections.push(connection));
+        }
+
+        let input_count = self.inputs.len();
+        let output_count = self.outputs.len();
+
+        let index_of = |point: &Point, is_target: bool| -> usize {
+            if let Some(i) = hidden.iter().position(|h| h == point) {
+                input_count + output_count + i
+            } else if is_target {
+                input_count + self.outputs.iter().position(|o| o == point).unwrap()
+            } else {
+                self.inputs.iter().position(|i| i == point).unwrap()
+            }
+        };
+
+        let indexed_connections: Vec<(usize, usize, f64)> = connections
+            .iter()
+            .map(|(from, to, weight)| (index_of(from, false), index_of(to, true), *weight))
+            .collect();
+
+        let (hidden_count, indexed_connections) =
+            prune_hidden(input_count, output_count, hidden.len(), indexed_connections);
+
+        phenotype(
+            (input_count, output_count, hidden_count),
+            &self.output_activation,
+            &self.hidden_activation,
+            indexed_connections,
+        )
+    }
+}
+
+/// Removes hidden nodes that aren't both reachable from the inputs and reaching the outputs
+fn prune_hidden(
+    input_count: usize,
+    output_count: usize,
+    hidden_count: usize,
+    connections: Vec<(usize, usize, f64)>,
+) -> (usize, Vec<(usize, usize, f64)>) {
+    let reachable = |starts: Vec<usize>, forward: bool| -> HashSet<usize> {
+        let mut visited: HashSet<usize> = HashSet::new();
+        let mut to_visit = starts;
+
+        while let Some(i) = to_visit.pop() {
+            if !visited.insert(i) {
+                continue;
+            }
+
+            connections
+                .iter()
+                .filter(|(from, to, _)| if forward { *from == i } else { *to == i })
+                .for_each(|(from, to, _)| to_visit.push(if forward { *to } else { *from }));
+        }
+
+        visited
+    };
+
+    let from_inputs = reachable((0..input_count).collect(), true);
+    let to_outputs = reachable((input_count..input_count + output_count).collect(), false);
+
+    let first_hidden = input_count + output_count;
+    let kept: Vec<usize> = (first_hidden..first_hidden + hidden_count)
+        .filter(|i| from_inputs.contains(i) && to_outputs.contains(i))
+        .collect();
+
+    let new_index = |i: usize| -> Option<usize> {
+        if i < first_hidden {
+            Some(i)
+        } else {
+            kept.iter().position(|k| *k == i).map(|p| first_hidden + p)
+        }
+    };
+
+    let kept_connections = connections
+        .iter()
+        .filter_map(|(from, to, weight)| Some((new_index(*from)?, new_index(*to)?, *weight)))
+        .collect();
+
+    (kept.len(), kept_connections)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aggregations::Aggregation;
+    use crate::genome::Genome;
+    use crate::hyperneat::CPPN_INPUTS;
+
+    /// A CPPN that outputs `activation(x1 + x2)` scaled by `weight`
+    fn cppn(weight: f64, activation: ActivationKind) -> Network {
+        let mut g = Genome::new(CPPN_INPUTS, 1);
+
+        (0..CPPN_INPUTS).for_each(|i| g.connection_mut(i).unwrap().weight = 0.);
+        g.connection_mut(0).unwrap().weight = weight;
+        g.connection_mut(2).unwrap().weight = weight;
+
+        let output = g.node_mut(CPPN_INPUTS).unwrap();
+        output.activation = activation;
+        output.aggregation = Aggregation::Sum;
+        output.bias = 0.;
+
+        Network::from(&g)
+    }
+
+    #[test]
+    fn uniform_cppn_discovers_nothing() {
+        let substrate = EvolvableSubstrate::new(vec![(-1., -1.), (1., -1.)], vec![(0., 1.)]);
+        let mut network = substrate.build_network(&mut cppn(0., ActivationKind::Gaussian));
+
+        assert_eq!(network.nodes.len(), 3);
+        assert!(network.connections.is_empty());
+        assert_eq!(network.forward_pass(&vec![1., 1.]).len(), 1);
+    }
+
+    #[test]
+    fn varying_cppn_discovers_hidden_nodes() {
+        let substrate = EvolvableSubstrate::new(vec![(0., -1.)], vec![(0., 1.)]);
+        let mut network = substrate.build_network(&mut cppn(4., ActivationKind::Gaussian));
+
+        let node_count = network.nodes.len();
+        assert!(node_count > 2);
+        assert!(network
+            .connections
+            .iter()
+            .all(|c| c.from < node_count && c.to < node_count && c.to >= 1));
+        assert_eq!(network.forward_pass(&vec![1.]).len(), 1);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::activation::ActivationKind;
+use crate::aggregations::Aggregation;
+use crate::genome::{ConnectionGene, Genome, NodeGene};
+use crate::neat::NEAT;
+use crate::network::Network;
+use crate::node::NodeKind;
+pub use evolvable_substrate::EvolvableSubstrate;
+pub use substrate::{Point, Substrate};
+
+mod evolvable_substrate;
+mod substrate;
+
+/// A CPPN is queried with the coordinates of both ends of a connection and a bias of 1
+pub const CPPN_INPUTS: usize = 5;
+
+impl NEAT {
+    /// Evolves CPPNs, the fitness function receives the network they paint onto the substrate
+    ///
+    /// `start` returns the best CPPN, `Substrate::build_network` turns it into the actual network.
+    pub fn with_substrate<F>(substrate: Substrate, fitness_fn: F) -> Self
+    where
+        F: Fn(&mut Network) -> f64 + Send + Sync + 'static,
+    {
+        substrate.assert_weight_threshold();
+
+        NEAT::new(CPPN_INPUTS, substrate.cppn_outputs(), move |cppn| {
+            let mut network = substrate.build_network(cppn);
+
+            fitness_fn(&mut network)
+        })
+    }
+
+    /// Like `with_substrate`, but the hidden nodes are discovered by ES-HyperNEAT
+    pub fn with_evolvable_substrate<F>(substrate: EvolvableSubstrate, fitness_fn: F) -> Self
+    where
+        F: Fn(&mut Network) -> f64 + Send + Sync + 'static,
+    {
+        NEAT::new(CPPN_INPUTS, 1, move |cppn| {
+            let mut network = substrate.build_network(cppn);
+
+            fitness_fn(&mut network)
+        })
+    }
+}
+
+/// The raw weight output of the CPPN for the connection between two points
+fn query_weight(cppn: &mut Network, from: Point, to: Point) -> f64 {
+    *cppn
+        .forward_pass(&vec![from.0, from.1, to.0, to.1, 1.])
+        .first()
+        .unwrap()
+}
+
+/// Asks the CPPN for the weight of the connection between two points, `None` if not expressed
+fn query_cppn(cppn: &mut Network, substrate: &Substrate, from: Point, to: Point) -> Option<f64> {
+    let outputs = cppn.forward_pass(&vec![from.0, from.1, to.0, to.1, 1.]);
+    let weight = *outputs.first().unwrap();
+
+    if substrate.expression_output && *outputs.get(1).unwrap() <= 0. {
+        return None;
+    }
+
+    if weight.is_nan() || weight.abs() <= substrate.weight_threshold {
+        return None;
+    }
+
+    // Weights start from zero right after the threshold
+    let scaled = (weight.abs() - substrate.weight_threshold) / (1. - substrate.weight_threshold);
+
+    Some(weight.signum() * scaled.min(1.) * substrate.max_weight)
+}
+
+/// Assembles the network, connections index inputs first, then outputs, then hidden nodes
+fn phenotype(
+    (input_count, output_count, hidden_count): (usize, usize, usize),
+    output_activation: &ActivationKind,
+    hidden_activation: &ActivationKind,
+    connections: Vec<(usize, usize, f64)>,
+) -> Network {
+    let node = |innovation: usize, (kind, activation): (NodeKind, ActivationKind)| NodeGene {
+        kind,
+        aggregation: Aggregation::Sum,
+        activation,
+        bias: 0.,
+        time_constant: 1.,
+        innovation,
+    };
+
+    let node_genes: Vec<NodeGene> = (0..input_count)
+        .map(|_| (NodeKind::Input, ActivationKind::Input))
+        .chain((0..output_count).map(|_| (NodeKind::Output, output_activation.clone())))
+        .chain((0..hidden_count).map(|_| (NodeKind::Hidden, hidden_activation.clone())))
+        .enumerate()
+        .map(|(innovation, kind_and_activation)| node(innovation, kind_and_activation))
+        .collect();
+
+    let connection_genes: Vec<ConnectionGene> = connections
+        .into_iter()
+        .enumerate()
+        .map(|(innovation, (from, to, weight))| ConnectionGene {
+            from,
+            to,
+            weight,
+            disabled: false,
+            innovation,
+        })
+        .collect();
+
+    let genome = Genome::from_genes(input_count, output_count, node_genes, connection_genes);
+
+    Network::from(&genome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::neat::Configuration;
+
+    /// A CPPN whose weight output is the x coordinate of the source
+    fn source_x_cppn() -> Network {
+        let mut g = Genome::new(CPPN_INPUTS, 1);
+
+        (0..CPPN_INPUTS).for_each(|i| g.connection_mut(i).unwrap().weight = 0.);
+        g.connection_mut(0).unwrap().weight = 1.;
+
+        let output = g.node_mut(CPPN_INPUTS).unwrap();
+        output.activation = ActivationKind::Identity;
+        output.aggregation = Aggregation::Sum;
+        output.bias = 0.;
+
+        Network::from(&g)
+    }
+
+    #[test]
+    fn query_respects_threshold() {
+        let substrate = Substrate::new(vec![(-1., 0.)], vec![], vec![(0., 1.)]);
+        let mut cppn = source_x_cppn();
+
+        assert_eq!(
+            query_cppn(&mut cppn, &substrate, (-1., 0.), (0., 1.)),
+            Some(-substrate.max_weight)
+        );
+        assert_eq!(query_cppn(&mut cppn, &substrate, (0.1, 0.), (0., 1.)), None);
+    }
+
+    #[test]
+    fn substrate_network_has_substrate_shape() {
+        let substrate = Substrate::new(vec![(-1., -1.), (1., -1.)], vec![], vec![(0., 1.)]);
+        let mut cppn = source_x_cppn();
+
+        let mut network = substrate.build_network(&mut cppn);
+
+        assert_eq!(network.nodes.len(), 3);
+        assert_eq!(network.connections.len(), 2);
+        assert_eq!(network.forward_pass(&vec![1., 1.]).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "The weight threshold has to be within [0, 1), got 1")]
+    fn weight_threshold_below_one() {
+        let mut substrate = Substrate::new(vec![(-1., 0.)], vec![], vec![(0., 1.)]);
+        substrate.weight_threshold = 1.;
+
+        substrate.build_network(&mut source_x_cppn());
+    }
+
+    #[test]
+    fn evolve_on_substrate() {
+        let substrate = Substrate::new(
+            vec![(-1., -1.), (1., -1.)],
+            vec![vec![(-1., 0.), (0., 0.), (1., 0.)]],
+            vec![(0., 1.)],
+        );
+
+        // Rewards CPPNs that express connections on the substrate
+        let mut system =
+            NEAT::with_substrate(substrate, |network| network.connections.len() as f64);
+
+        system.set_configuration(Configuration {
+            population_size: 20,
+            max_generations: 5,
+            ..Default::default()
+        });
+
+        let (_, fitness) = system.start();
+        assert!(fitness > 0.);
+    }
+}
@@ -0,0 +1,118 @@
+use super::{phenotype, query_cppn};
+use crate::activation::ActivationKind;
+use crate::network::Network;
+
+/// Coordinates of a substrate node, usually within [-1, 1] on both axes
+pub type Point = (f64, f64);
+
+/// The geometry the CPPNs paint their weights onto
+#[derive(Debug, Clone)]
+pub struct Substrate {
+    pub inputs: Vec<Point>,
+    /// Every layer is connected to the next one, the first to the inputs and the last to the outputs
+    pub hidden_layers: Vec<Vec<Point>>,
+    pub outputs: Vec<Point>,
+
+    /// CPPN outputs with a smaller magnitude don't create a connection, within [0, 1)
+    pub weight_threshold: f64,
+
+    /// Magnitude of the weight for the strongest CPPN output
+    pub max_weight: f64,
+
+    pub hidden_activation: ActivationKind,
+    pub output_activation: ActivationKind,
+
+    /// Connections are only expressed if the second CPPN output is positive
+    pub expression_output: bool,
+}
+
+impl Substrate {
+    pub fn new(inputs: Vec<Point>, hidden_layers: Vec<Vec<Point>>, outputs: Vec<Point>) -> Self {
+        Substrate {
+            inputs,
+            hidden_layers,
+            outputs,
+            weight_threshold: 0.2,
+            max_weight: 5.,
+            hidden_activation: ActivationKind::Tanh,
+            output_activation: ActivationKind::Tanh,
+            expression_output: false,
+        }
+    }
+
+    /// Number of outputs the evolved CPPNs need
+    pub fn cppn_outputs(&self) -> usize {
+        if self.expression_output {
+            2
+        } else {
+            1
+        }
+    }
+
+    pub(super) fn assert_weight_threshold(&self) {
+        assert!(
+            (0. ..1.).contains(&self.weight_threshold),
+            "The weight threshold has to be within [0, 1), got {}",
+            self.weight_threshold
+        );
+    }
+
+    /// Queries the CPPN for every pair of nodes in neighbouring layers
+    pub fn build_network(&self, cppn: &mut Network) -> Network {
+        self.assert_weight_threshold();
+
+        let input_count = self.inputs.len();
+        let output_count = self.outputs.len();
+
+        // Node indexes of every layer, hidden nodes come after the outputs
+        let mut layers: Vec<Vec<(usize, Point)>> =
+            vec![self.inputs.iter().cloned().enumerate().collect()];
+
+        let mut next_hidden_index = input_count + output_count;
+        self.hidden_layers.iter().for_each(|layer| {
+            layers.push(
+                layer
+                    .iter()
+                    .map(|point| {
+                        next_hidden_index += 1;
+                        (next_hidden_index - 1, *point)
+                    })
+                    .collect(),
+            );
+        });
+
+        layers.push(
+            self.outputs
+                .iter()
+                .enumerate()
+                .map(|(i, point)| (input_count + i, *point))
+                .collect(),
+        );
+
+        let connections: Vec<(usize, usize, f64)> = layers
+            .windows(2)
+            .flat_map(|pair| {
+                let (sources, targets) = (&pair[0], &pair[1]);
+
+                sources
+                    .iter()
+                    .flat_map(|source| targets.iter().map(move |target| (*source, *target)))
+                    .collect::<Vec<((usize, Point), (usize, Point))>>()
+            })
+            .filter_map(|((from, from_point), (to, to_point))| {
+                query_cppn(cppn, self, from_point, to_point).map(|weight| (from, to, weight))
+            })
+            .collect();
+
+        phenotype(
+            (
+                input_count,
+                output_count,
+                next_hidden_index - input_count - output_count,
+            ),
+            &self.output_activation,
+            &self.hidden_activation,
+            connections,
+        )
+    }
+}
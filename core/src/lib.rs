@@ -2,13 +2,22 @@ mod activation;
 mod aggregations;
 mod connection;
 mod genome;
+pub mod hyperneat;
+pub mod map_elites;
 mod mutations;
 mod neat;
 mod network;
 mod node;
 pub mod reporting;
+mod rng;
 mod speciation;
 
+pub use activation::ActivationKind;
+pub use aggregations::Aggregation;
 pub use genome::*;
+pub use mutations::MutationKind;
 pub use neat::*;
 pub use network::*;
+
+/// Networks can be built for any float type implementing this trait
+pub use num_traits::Float;
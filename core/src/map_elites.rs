@@ -0,0 +1,389 @@
+use rand::Rng;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::genome::{crossover, Genome, InnovationTracker};
+use crate::mutations::apply_node_options;
+use crate::neat::{pick_mutation, Configuration};
+use crate::network::Network;
+use crate::rng::{self, NeatRng};
+
+/// Returns the fitness of the network and its behaviour descriptor, one value per grid dimension
+pub type DescriptorFn = dyn Fn(&mut Network) -> (f64, Vec<f64>) + Send + Sync;
+
+/// Position of a cell, one index per grid dimension
+pub type Cell = Vec<usize>;
+
+/// One axis of the grid, descriptors outside of the range fall into the cells at its ends
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dimension {
+    pub min: f64,
+    pub max: f64,
+    pub cells: usize,
+}
+
+impl Dimension {
+    pub fn new(min: f64, max: f64, cells: usize) -> Self {
+        assert!(min < max, "The range of a dimension can't be empty");
+        assert!(cells > 0, "A dimension needs at least one cell");
+
+        Dimension { min, max, cells }
+    }
+
+    fn index(&self, value: f64) -> Option<usize> {
+        if value.is_nan() {
+            return None;
+        }
+
+        let position = (value - self.min) / (self.max - self.min) * self.cells as f64;
+
+        Some((position.max(0.) as usize).min(self.cells - 1))
+    }
+}
+
+/// The best genome found for a cell
+#[derive(Debug, Clone)]
+pub struct Elite {
+    pub genome: Genome,
+    pub fitness: f64,
+    pub descriptor: Vec<f64>,
+}
+
+/// Statistics of the archive after a batch, returned by `MapElites::step`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveSummary {
+    pub iteration: usize,
+    pub filled_cells: usize,
+    /// Share of the cells that hold an elite
+    pub coverage: f64,
+    /// Sum of how far the fitness of every elite is above `qd_score_min_fitness`, it never
+    /// decreases as cells fill or elites improve
+    pub qd_score: f64,
+    pub best_fitness: f64,
+}
+
+/// MAP-Elites, keeps the best genome for every cell of a grid of behaviour descriptors instead of a
+/// single champion
+///
+/// The configuration is shared with `NEAT`, `population_size` is the number of genomes evaluated
+/// in every batch and `max_generations` the number of batches `start` runs.
+pub struct MapElites {
+    inputs: usize,
+    outputs: usize,
+    dimensions: Vec<Dimension>,
+    descriptor_fn: Box<DescriptorFn>,
+    configuration: Arc<Configuration>,
+    elites: BTreeMap<Cell, Elite>,
+    innovations: InnovationTracker,
+    rng: NeatRng,
+    iteration: usize,
+    initialized: bool,
+}
+
+impl MapElites {
+    pub fn new<F>(
+        inputs: usize,
+        outputs: usize,
+        dimensions: Vec<Dimension>,
+        descriptor_fn: F,
+    ) -> Self
+    where
+        F: Fn(&mut Network) -> (f64, Vec<f64>) + Send + Sync + 'static,
+    {
+        assert!(
+            !dimensions.is_empty(),
+            "The grid needs at least one dimension"
+        );
+
+        MapElites {
+            inputs,
+            outputs,
+            dimensions,
+            descriptor_fn: Box::new(descriptor_fn),
+            configuration: Default::default(),
+            elites: BTreeMap::new(),
+            innovations: InnovationTracker::new(inputs, outputs),
+            rng: rng::from_seed(None),
+            iteration: 0,
+            initialized: false,
+        }
+    }
+
+    pub fn set_configuration(&mut self, configuration: Configuration) {
+        self.configuration = Arc::new(configuration);
+    }
+
+    pub fn configuration(&self) -> &Configuration {
+        &self.configuration
+    }
+
+    /// Evaluates random genomes to fill the first cells, discarding any previous archive
+    pub fn initialize(&mut self) {
+        self.rng = rng::from_seed(self.configuration.seed);
+        self.innovations = InnovationTracker::new(self.inputs, self.outputs);
+        self.elites = BTreeMap::new();
+        self.iteration = 0;
+        self.initialized = true;
+
+        let genomes = self.random_genomes();
+        self.insert(genomes);
+    }
+
+    fn random_genomes(&mut self) -> Vec<Genome> {
+        (0..self.configuration.population_size)
+            .map(|_| {
+                let mut genome = Genome::with_topology(
+                    self.inputs,
+                    self.outputs,
+                    &self.configuration.initial_topology,
+                    &mut self.rng,
+                );
+                apply_node_options(&mut genome, &self.configuration, &mut self.rng);
+
+                genome
+            })
+            .collect()
+    }
+
+    /// Creates and evaluates a batch of children of random elites, or of random genomes while the
+    /// archive is still empty
+    pub fn step(&mut self) -> ArchiveSummary {
+        if !self.initialized {
+            self.initialize();
+        }
+
+        let genomes = if self.elites.is_empty() {
+            self.random_genomes()
+        } else {
+            self.children()
+        };
+
+        self.insert(genomes);
+        self.iteration += 1;
+
+        self.summary()
+    }
+
+    /// Mutated children of random elites, needs at least one elite
+    fn children(&mut self) -> Vec<Genome> {
+        let configuration = self.configuration.clone();
+        let rng = &mut self.rng;
+        let innovations = &mut self.innovations;
+        innovations.new_generation();
+
+        let parents: Vec<&Elite> = self.elites.values().collect();
+        (0..configuration.population_size)
+            .map(|_| {
+                let parent_a = parents[rng.gen::<usize>() % parents.len()];
+                let parent_b = parents[rng.gen::<usize>() % parents.len()];
+
+                let mut child = crossover(
+                    (&parent_a.genome, parent_a.fitness),
+                    (&parent_b.genome, parent_b.fitness),
+                    &configuration,
+                    rng,
+                )
+                .unwrap_or_else(|| parent_a.genome.copy_with_new_id(rng));
+
+                // Every child differs from its parents, otherwise the batch is wasted
+                let mutation = pick_mutation(&configuration.mutation_kinds, rng);
+                child.mutate(&mutation, &configuration, innovations, rng);
+
+                child
+            })
+            .collect()
+    }
+
+    /// Runs the configured number of batches and returns the final statistics
+    pub fn start(&mut self) -> ArchiveSummary {
+        if !self.initialized {
+            self.initialize();
+        }
+
+        while self.iteration < self.configuration.max_generations {
+            self.step();
+        }
+
+        self.summary()
+    }
+
+    /// Evaluates the genomes and keeps every one that beats the elite of its cell
+    fn insert(&mut self, genomes: Vec<Genome>) {
+        let descriptor_fn = &self.descriptor_fn;
+        let non_finite_fitness = self.configuration.non_finite_fitness;
+
+        let evaluations: Vec<(f64, Vec<f64>)> = genomes
+            .par_iter()
+            .map(|genome| {
+                let (fitness, descriptor) = (descriptor_fn)(&mut Network::from(genome));
+
+                if fitness.is_finite() {
+                    (fitness, descriptor)
+                } else {
+                    (non_finite_fitness, descriptor)
+                }
+            })
+            .collect();
+
+        for (genome, (fitness, descriptor)) in genomes.into_iter().zip(evaluations) {
+            let cell = match self.cell(&descriptor) {
+                Some(cell) => cell,
+                None => continue,
+            };
+
+            let is_better = match self.elites.get(&cell) {
+                Some(elite) => fitness > elite.fitness,
+                None => true,
+            };
+
+            if is_better {
+                self.elites.insert(
+                    cell,
+                    Elite {
+                        genome,
+                        fitness,
+                        descriptor,
+                    },
+                );
+            }
+        }
+    }
+
+    /// The cell a descriptor falls into, `None` if it doesn't fit the grid
+    pub fn cell(&self, descriptor: &[f64]) -> Option<Cell> {
+        if descriptor.len() != self.dimensions.len() {
+            return None;
+        }
+
+        self.dimensions
+            .iter()
+            .zip(descriptor)
+            .map(|(dimension, value)| dimension.index(*value))
+            .collect()
+    }
+
+    pub fn elites(&self) -> &BTreeMap<Cell, Elite> {
+        &self.elites
+    }
+
+    /// Number of batches run since initialization
+    pub fn iteration(&self) -> usize {
+        self.iteration
+    }
+
+    pub fn summary(&self) -> ArchiveSummary {
+        let cell_count: usize = self.dimensions.iter().map(|d| d.cells).product();
+        let fitnesses = self.elites.values().map(|elite| elite.fitness);
+        let min_fitness = self.configuration.qd_score_min_fitness;
+
+        ArchiveSummary {
+            iteration: self.iteration,
+            filled_cells: self.elites.len(),
+            coverage: self.elites.len() as f64 / cell_count as f64,
+            qd_score: fitnesses
+                .clone()
+                .map(|f| f64::max(f - min_fitness, 0.))
+                .sum(),
+            best_fitness: fitnesses.fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map_elites() -> MapElites {
+        let mut map_elites = MapElites::new(
+            2,
+            1,
+            vec![Dimension::new(-1., 1., 5), Dimension::new(-1., 1., 5)],
+            |n| {
+                let first = n.forward_pass(&vec![1., 0.])[0];
+                let second = n.forward_pass(&vec![0., 1.])[0];
+
+                (-(first - second).abs(), vec![first, second])
+            },
+        );
+
+        map_elites.set_configuration(Configuration {
+            population_size: 20,
+            max_generations: 10,
+            seed: Some(21),
+            qd_score_min_fitness: -1.,
+            ..Default::default()
+        });
+
+        map_elites
+    }
+
+    #[test]
+    fn descriptors_map_to_cells() {
+        let map_elites = map_elites();
+
+        assert_eq!(map_elites.cell(&[-1., 1.]), Some(vec![0, 4]));
+        assert_eq!(map_elites.cell(&[0., 0.3]), Some(vec![2, 3]));
+        assert_eq!(map_elites.cell(&[-5., 5.]), Some(vec![0, 4]));
+        assert_eq!(map_elites.cell(&[f64::NAN, 0.]), None);
+        assert_eq!(map_elites.cell(&[0.]), None);
+    }
+
+    #[test]
+    fn elites_only_get_better() {
+        let mut map_elites = map_elites();
+        map_elites.initialize();
+
+        let mut previous = map_elites.elites().clone();
+        let mut previous_qd_score = map_elites.summary().qd_score;
+        for _ in 0..10 {
+            let summary = map_elites.step();
+            assert!(summary.qd_score >= previous_qd_score);
+
+            for (cell, elite) in &previous {
+                assert!(map_elites.elites()[cell].fitness >= elite.fitness);
+            }
+            for (cell, elite) in map_elites.elites() {
+                assert_eq!(map_elites.cell(&elite.descriptor).as_ref(), Some(cell));
+            }
+
+            let qd_score: f64 = map_elites
+                .elites()
+                .values()
+                .map(|e| f64::max(e.fitness + 1., 0.))
+                .sum();
+            assert!((summary.qd_score - qd_score).abs() < 1e-9);
+            assert_eq!(summary.coverage, summary.filled_cells as f64 / 25.);
+
+            previous = map_elites.elites().clone();
+            previous_qd_score = summary.qd_score;
+        }
+    }
+
+    #[test]
+    fn empty_archive_keeps_sampling() {
+        let mut map_elites = MapElites::new(2, 1, vec![Dimension::new(0., 1., 5)], |n| {
+            let output = n.forward_pass(&vec![1., 0.])[0];
+
+            (output, vec![f64::NAN])
+        });
+        map_elites.set_configuration(Configuration {
+            population_size: 10,
+            max_generations: 3,
+            ..Default::default()
+        });
+
+        let summary = map_elites.start();
+
+        assert_eq!(summary.iteration, 3);
+        assert_eq!(summary.filled_cells, 0);
+    }
+
+    #[test]
+    fn seeded_runs_are_identical() {
+        let mut a = map_elites();
+        let mut b = map_elites();
+
+        assert_eq!(a.start(), b.start());
+    }
+}
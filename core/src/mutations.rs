@@ -1,29 +1,83 @@
 use rand::distributions::{Distribution, Standard};
-use rand::random;
-use rand::thread_rng;
 use rand::Rng;
 use rand_distr::StandardNormal;
 
 use crate::activation::ActivationKind;
-use crate::genome::Genome;
+use crate::aggregations::Aggregation;
+use crate::genome::{Genome, InnovationTracker};
+use crate::neat::Configuration;
 use crate::node::NodeKind;
 
-pub fn mutate(kind: &MutationKind, g: &mut Genome) {
+/// Bounds of the time constants, zero would make a node react infinitely fast
+const MIN_TIME_CONSTANT: f64 = 0.01;
+const MAX_TIME_CONSTANT: f64 = 10.;
+
+pub fn mutate<R: Rng + ?Sized>(
+    kind: &MutationKind,
+    g: &mut Genome,
+    configuration: &Configuration,
+    innovations: &mut InnovationTracker,
+    rng: &mut R,
+) {
     use MutationKind::*;
 
     match kind {
-        AddConnection => add_connection(g),
-        RemoveConnection => disable_connection(g),
-        AddNode => add_node(g),
-        RemoveNode => remove_node(g),
-        ModifyWeight => change_weight(g),
-        ModifyBias => change_bias(g),
-        ModifyActivation => change_activation(g),
-        ModifyAggregation => change_aggregation(g),
+        AddConnection => add_connection(g, configuration.recurrent_connections, innovations, rng),
+        RemoveConnection => disable_connection(g, rng),
+        AddNode => {
+            add_node(g, innovations, rng);
+            apply_node_options(g, configuration, rng);
+        }
+        RemoveNode => remove_node(g, configuration.recurrent_connections, innovations, rng),
+        ModifyWeight => change_weight(g, rng),
+        ModifyBias => change_bias(g, rng),
+        ModifyTimeConstant => change_time_constant(g, rng),
+        ModifyActivation => change_activation(g, &configuration.activation_options, rng),
+        ModifyAggregation => change_aggregation(g, &configuration.aggregation_options, rng),
     };
 }
 
+/// Picks one of the options, or any kind when there are none
+fn pick_option<T: Clone, R: Rng + ?Sized>(options: &[T], rng: &mut R) -> T
+where
+    Standard: Distribution<T>,
+{
+    if options.is_empty() {
+        rng.gen()
+    } else {
+        options[rng.gen::<usize>() % options.len()].clone()
+    }
+}
+
+/// Replaces activations and aggregations of non input nodes that aren't among the options
+pub(crate) fn apply_node_options<R: Rng + ?Sized>(
+    g: &mut Genome,
+    configuration: &Configuration,
+    rng: &mut R,
+) {
+    let activations = &configuration.activation_options;
+    let aggregations = &configuration.aggregation_options;
+
+    for index in 0..g.nodes().len() {
+        let node = g.node_mut(index).unwrap();
+        if matches!(node.kind, NodeKind::Input) {
+            continue;
+        }
+
+        if !activations.is_empty() && !activations.contains(&node.activation) {
+            node.activation = pick_option(activations, rng);
+        }
+        if !aggregations.is_empty() && !aggregations.contains(&node.aggregation) {
+            node.aggregation = pick_option(aggregations, rng);
+        }
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    any(feature = "checkpoint", feature = "config-file"),
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub enum MutationKind {
     AddConnection,
     RemoveConnection,
@@ -31,6 +85,8 @@ pub enum MutationKind {
     RemoveNode,
     ModifyWeight,
     ModifyBias,
+    /// Only matters for networks advanced in continuous time, not part of the default kinds
+    ModifyTimeConstant,
     ModifyActivation,
     ModifyAggregation,
 }
@@ -51,8 +107,13 @@ impl Distribution<MutationKind> for Standard {
     }
 }
 
-/// Adds a new random connection
-pub fn add_connection(g: &mut Genome) {
+/// Adds a new random connection, cycles and self connections are only allowed if `recurrent`
+pub fn add_connection<R: Rng + ?Sized>(
+    g: &mut Genome,
+    recurrent: bool,
+    innovations: &mut InnovationTracker,
+    rng: &mut R,
+) {
     let existing_connections: Vec<(usize, usize, bool)> = g
         .connections()
         .iter()
@@ -64,7 +125,7 @@ pub fn add_connection(g: &mut Genome) {
             let mut inner = vec![];
 
             (0..g.nodes().len()).for_each(|j| {
-                if i != j {
+                if i != j || recurrent {
                     if !existing_connections.contains(&(i, j, false)) {
                         inner.push((i, j));
                     };
@@ -83,7 +144,13 @@ pub fn add_connection(g: &mut Genome) {
 
     possible_connections = possible_connections
         .into_iter()
-        .filter(|(i, j)| g.can_connect(*i, *j))
+        .filter(|(i, j)| {
+            if recurrent {
+                g.can_connect_recurrent(*i, *j)
+            } else {
+                g.can_connect(*i, *j)
+            }
+        })
         .collect();
 
     if possible_connections.is_empty() {
@@ -91,15 +158,14 @@ pub fn add_connection(g: &mut Genome) {
     }
 
     let picked_connection = possible_connections
-        .get(random::<usize>() % possible_connections.len())
+        .get(rng.gen::<usize>() % possible_connections.len())
         .unwrap();
 
-    g.add_connection(picked_connection.0, picked_connection.1)
-        .unwrap();
+    g.insert_connection(picked_connection.0, picked_connection.1, innovations, rng);
 }
 
 /// Removes a random connection if it's not the only one
-fn disable_connection(g: &mut Genome) {
+fn disable_connection<R: Rng + ?Sized>(g: &mut Genome, rng: &mut R) {
     let eligible_indexes: Vec<usize> = g
         .connections()
         .iter()
@@ -135,16 +201,14 @@ fn disable_connection(g: &mut Genome) {
     }
 
     let index = eligible_indexes
-        .get(random::<usize>() % eligible_indexes.len())
+        .get(rng.gen::<usize>() % eligible_indexes.len())
         .unwrap();
 
     g.disable_connection(*index);
 }
 
 /// Adds a random hidden node to the genome and its connections
-pub fn add_node(g: &mut Genome) {
-    let new_node_index = g.add_node();
-
+pub fn add_node<R: Rng + ?Sized>(g: &mut Genome, innovations: &mut InnovationTracker, rng: &mut R) {
     // Only enabled connections can be disabled
     let enabled_connections: Vec<usize> = g
         .connections()
@@ -154,8 +218,12 @@ pub fn add_node(g: &mut Genome) {
         .map(|(i, _)| i)
         .collect();
 
-    let (picked_index, picked_from, picked_to, picked_weight) = {
-        let random_enabled_connection_index = random::<usize>() % enabled_connections.len();
+    if enabled_connections.is_empty() {
+        return;
+    }
+
+    let (picked_index, picked_from, picked_to, picked_weight, picked_innovation) = {
+        let random_enabled_connection_index = rng.gen::<usize>() % enabled_connections.len();
         let picked_index = enabled_connections
             .get(random_enabled_connection_index)
             .unwrap();
@@ -166,20 +234,34 @@ pub fn add_node(g: &mut Genome) {
             picked_connection.from,
             picked_connection.to,
             picked_connection.weight,
+            picked_connection.innovation,
         )
     };
 
+    // A connection that was split before and enabled again gets a node of its own
+    let mut node_innovation = innovations.split_node(picked_innovation);
+    if g.nodes().iter().any(|n| n.innovation == node_innovation) {
+        node_innovation = innovations.node();
+    }
+    let new_node_index = g.insert_node(node_innovation, rng);
+
     g.disable_connection(*picked_index);
 
-    let connection_index = g.add_connection(picked_from, new_node_index).unwrap();
-    g.add_connection(new_node_index, picked_to).unwrap();
+    // Splitting a connection never creates a new cycle, recurrent ones stay recurrent
+    let connection_index = g.insert_connection(picked_from, new_node_index, innovations, rng);
+    g.insert_connection(new_node_index, picked_to, innovations, rng);
 
     // Reuse the weight from the removed connection
     g.connection_mut(connection_index).unwrap().weight = picked_weight;
 }
 
 /// Removes a random hidden node from the genome and rewires connected nodes
-fn remove_node(g: &mut Genome) {
+fn remove_node<R: Rng + ?Sized>(
+    g: &mut Genome,
+    recurrent: bool,
+    innovations: &mut InnovationTracker,
+    rng: &mut R,
+) {
     let hidden_nodes: Vec<usize> = g
         .nodes()
         .iter()
@@ -206,7 +288,7 @@ fn remove_node(g: &mut Genome) {
     }
 
     let picked_node_index = hidden_nodes
-        .get(random::<usize>() % hidden_nodes.len())
+        .get(rng.gen::<usize>() % hidden_nodes.len())
         .unwrap();
 
     let incoming_connections_and_from_indexes: Vec<(usize, usize)> = g
@@ -232,6 +314,8 @@ fn remove_node(g: &mut Genome) {
                 .map(|(_, to)| (*from, *to))
                 .collect::<Vec<(usize, usize)>>()
         })
+        // A self connection of the removed node would only connect it again
+        .filter(|(from, to)| *from != *picked_node_index && *to != *picked_node_index)
         .filter(|(from, to)| {
             g.connections()
                 .iter()
@@ -240,7 +324,17 @@ fn remove_node(g: &mut Genome) {
         })
         .collect();
 
-    g.add_many_connections(&new_from_to_pairs);
+    for (from, to) in new_from_to_pairs {
+        let can_connect = if recurrent {
+            g.can_connect_recurrent(from, to)
+        } else {
+            g.can_connect(from, to)
+        };
+
+        if can_connect {
+            g.insert_connection(from, to, innovations, rng);
+        }
+    }
 
     let connection_indexes_to_delete: Vec<usize> = g
         .connections()
@@ -254,21 +348,25 @@ fn remove_node(g: &mut Genome) {
 }
 
 /// Changes the weight of a random connection
-fn change_weight(g: &mut Genome) {
-    let index = random::<usize>() % g.connections().len();
+fn change_weight<R: Rng + ?Sized>(g: &mut Genome, rng: &mut R) {
+    if g.connections().is_empty() {
+        return;
+    }
+
+    let index = rng.gen::<usize>() % g.connections().len();
     let picked_connection = g.connection_mut(index).unwrap();
 
-    let new_weight = if random::<f64>() < 0.1 {
-        picked_connection.weight + thread_rng().sample::<f64, StandardNormal>(StandardNormal)
+    let new_weight = if rng.gen::<f64>() < 0.1 {
+        picked_connection.weight + rng.sample::<f64, _>(StandardNormal)
     } else {
-        random::<f64>() * 2. - 1.
+        rng.gen::<f64>() * 2. - 1.
     };
 
     picked_connection.weight = new_weight.max(-1.).min(1.);
 }
 
 /// Changes the bias of a random non input node
-fn change_bias(g: &mut Genome) {
+fn change_bias<R: Rng + ?Sized>(g: &mut Genome, rng: &mut R) {
     let eligible_indexes: Vec<usize> = g
         .nodes()
         .iter()
@@ -278,21 +376,45 @@ fn change_bias(g: &mut Genome) {
         .collect();
 
     let index = eligible_indexes
-        .get(random::<usize>() % eligible_indexes.len())
+        .get(rng.gen::<usize>() % eligible_indexes.len())
         .unwrap();
     let picked_node = g.node_mut(*index).unwrap();
 
-    let new_bias = if random::<f64>() < 0.1 {
-        picked_node.bias + thread_rng().sample::<f64, StandardNormal>(StandardNormal)
+    let new_bias = if rng.gen::<f64>() < 0.1 {
+        picked_node.bias + rng.sample::<f64, _>(StandardNormal)
     } else {
-        random::<f64>() * 2. - 1.
+        rng.gen::<f64>() * 2. - 1.
     };
 
     picked_node.bias = new_bias.max(-1.).min(1.);
 }
 
+/// Changes the time constant of a random non input node
+fn change_time_constant<R: Rng + ?Sized>(g: &mut Genome, rng: &mut R) {
+    let eligible_indexes: Vec<usize> = g
+        .nodes()
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| !matches!(n.kind, NodeKind::Input))
+        .map(|(i, _)| i)
+        .collect();
+
+    let index = eligible_indexes
+        .get(rng.gen::<usize>() % eligible_indexes.len())
+        .unwrap();
+    let picked_node = g.node_mut(*index).unwrap();
+
+    let new_time_constant = if rng.gen::<f64>() < 0.1 {
+        rng.gen::<f64>() * MAX_TIME_CONSTANT
+    } else {
+        picked_node.time_constant + rng.sample::<f64, _>(StandardNormal) * 0.1
+    };
+
+    picked_node.time_constant = new_time_constant.clamp(MIN_TIME_CONSTANT, MAX_TIME_CONSTANT);
+}
+
 /// Changes the activation function of a random non input node
-fn change_activation(g: &mut Genome) {
+fn change_activation<R: Rng + ?Sized>(g: &mut Genome, options: &[ActivationKind], rng: &mut R) {
     let eligible_indexes: Vec<usize> = g
         .nodes()
         .iter()
@@ -302,14 +424,14 @@ fn change_activation(g: &mut Genome) {
         .collect();
 
     let index = eligible_indexes
-        .get(random::<usize>() % eligible_indexes.len())
+        .get(rng.gen::<usize>() % eligible_indexes.len())
         .unwrap();
     let picked_node = g.node_mut(*index).unwrap();
 
-    picked_node.activation = random::<ActivationKind>();
+    picked_node.activation = pick_option(options, rng);
 }
 
-fn change_aggregation(g: &mut Genome) {
+fn change_aggregation<R: Rng + ?Sized>(g: &mut Genome, options: &[Aggregation], rng: &mut R) {
     let eligible_indexes: Vec<usize> = g
         .nodes()
         .iter()
@@ -319,27 +441,29 @@ fn change_aggregation(g: &mut Genome) {
         .collect();
 
     let index = eligible_indexes
-        .get(random::<usize>() % eligible_indexes.len())
+        .get(rng.gen::<usize>() % eligible_indexes.len())
         .unwrap();
     let picked_node = g.node_mut(*index).unwrap();
 
-    picked_node.aggregation = random();
+    picked_node.aggregation = pick_option(options, rng);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::{random, thread_rng};
 
     #[test]
     fn add_connection_adds_missing_connection() {
         let mut g = Genome::new(1, 2);
 
-        g.add_node();
-        g.add_connection(0, 3).unwrap();
-        g.add_connection(3, 2).unwrap();
+        g.add_node(&mut thread_rng());
+        g.add_connection(0, 3, &mut thread_rng()).unwrap();
+        g.add_connection(3, 2, &mut thread_rng()).unwrap();
 
         assert!(!g.connections().iter().any(|c| c.from == 3 && c.to == 1));
-        add_connection(&mut g);
+        let mut innovations = InnovationTracker::for_genome(&g);
+        add_connection(&mut g, false, &mut innovations, &mut thread_rng());
         assert!(g.connections().iter().any(|c| c.from == 3 && c.to == 1));
     }
 
@@ -347,26 +471,40 @@ mod tests {
     fn add_connection_doesnt_add_unecessary_connections() {
         let mut g = Genome::new(1, 2);
 
-        g.add_node();
-        g.add_connection(0, 3).unwrap();
-        g.add_connection(3, 2).unwrap();
+        g.add_node(&mut thread_rng());
+        g.add_connection(0, 3, &mut thread_rng()).unwrap();
+        g.add_connection(3, 2, &mut thread_rng()).unwrap();
 
         // This will add the last missing connection
         assert_eq!(g.connections().len(), 4);
-        add_connection(&mut g);
+        let mut innovations = InnovationTracker::for_genome(&g);
+        add_connection(&mut g, false, &mut innovations, &mut thread_rng());
         assert_eq!(g.connections().len(), 5);
 
         // There should be no new connections
-        add_connection(&mut g);
+        add_connection(&mut g, false, &mut innovations, &mut thread_rng());
         assert_eq!(g.connections().len(), 5);
     }
 
+    #[test]
+    fn add_connection_adds_self_connection_when_recurrent() {
+        let mut g = Genome::new(1, 1);
+
+        // The only connection missing is the output connecting to itself
+        let mut innovations = InnovationTracker::for_genome(&g);
+        add_connection(&mut g, false, &mut innovations, &mut thread_rng());
+        assert_eq!(g.connections().len(), 1);
+
+        add_connection(&mut g, true, &mut innovations, &mut thread_rng());
+        assert!(g.connections().iter().any(|c| c.from == 1 && c.to == 1));
+    }
+
     #[test]
     fn remove_connection_doesnt_remove_last_connection_of_a_node() {
         let mut g = Genome::new(1, 2);
         assert_eq!(g.connections().iter().filter(|c| !c.disabled).count(), 2);
 
-        disable_connection(&mut g);
+        disable_connection(&mut g, &mut thread_rng());
         assert_eq!(g.connections().iter().filter(|c| !c.disabled).count(), 2);
     }
 
@@ -375,7 +513,9 @@ mod tests {
         let mut g = Genome::new(1, 1);
         let original_connections = g.connections().to_vec();
 
-        add_node(&mut g);
+        let mut innovations = InnovationTracker::for_genome(&g);
+
+        add_node(&mut g, &mut innovations, &mut thread_rng());
 
         let original_connections_not_modified = original_connections
             .iter()
@@ -399,10 +539,12 @@ mod tests {
         let mut g = Genome::new(1, 1);
         let connection_enabled_initially = !g.connections().first().unwrap().disabled;
 
-        add_node(&mut g);
+        let mut innovations = InnovationTracker::for_genome(&g);
+
+        add_node(&mut g, &mut innovations, &mut thread_rng());
         let connection_disabled_after_add = g.connections().first().unwrap().disabled;
 
-        remove_node(&mut g);
+        remove_node(&mut g, false, &mut innovations, &mut thread_rng());
         let connection_enabled_after_remove = !g.connections().first().unwrap().disabled;
 
         assert!(connection_enabled_initially);
@@ -410,6 +552,29 @@ mod tests {
         assert!(connection_enabled_after_remove);
     }
 
+    #[test]
+    fn remove_node_keeps_recurrent_paths_when_allowed() {
+        let mut rng = thread_rng();
+
+        // The output feeds back into the hidden node, removing it leaves the output connecting to
+        // itself
+        let mut g = Genome::new(1, 1);
+        let mut innovations = InnovationTracker::for_genome(&g);
+        add_node(&mut g, &mut innovations, &mut rng);
+        g.add_recurrent_connection(1, 2, &mut rng).unwrap();
+
+        remove_node(&mut g, true, &mut innovations, &mut rng);
+
+        assert!(g
+            .connections()
+            .iter()
+            .any(|c| c.from == 1 && c.to == 1 && !c.disabled));
+        assert!(g
+            .connections()
+            .iter()
+            .all(|c| c.disabled || (c.from != 2 && c.to != 2)));
+    }
+
     #[test]
     fn change_bias_doesnt_change_input_nodes() {
         let mut g = Genome::new(1, 1);
@@ -418,7 +583,7 @@ mod tests {
         let output_bias = g.nodes().get(1).unwrap().bias;
 
         for _ in 0..10 {
-            change_bias(&mut g);
+            change_bias(&mut g, &mut thread_rng());
         }
 
         let new_input_bias = g.nodes().get(0).unwrap().bias;
@@ -428,6 +593,22 @@ mod tests {
         assert!((output_bias - new_output_bias).abs() > f64::EPSILON);
     }
 
+    #[test]
+    fn change_time_constant_stays_in_bounds() {
+        let mut g = Genome::new(1, 1);
+
+        for _ in 0..100 {
+            change_time_constant(&mut g, &mut thread_rng());
+        }
+
+        let input_time_constant = g.nodes().first().unwrap().time_constant;
+        let output_time_constant = g.nodes().get(1).unwrap().time_constant;
+
+        assert!((input_time_constant - 1.).abs() < f64::EPSILON);
+        assert!(output_time_constant >= MIN_TIME_CONSTANT);
+        assert!(output_time_constant <= MAX_TIME_CONSTANT);
+    }
+
     #[test]
     fn change_activation_doesnt_change_input_nodes() {
         let mut g = Genome::new(1, 1);
@@ -439,7 +620,7 @@ mod tests {
         let mut new_o_activations = vec![];
 
         for _ in 0..10 {
-            change_activation(&mut g);
+            change_activation(&mut g, &[], &mut thread_rng());
 
             new_i_activations.push(g.nodes().get(0).unwrap().activation.clone());
             new_o_activations.push(g.nodes().get(1).unwrap().activation.clone());
@@ -449,6 +630,49 @@ mod tests {
         assert!(new_o_activations.iter().any(|a| *a != o_activation));
     }
 
+    #[test]
+    fn node_options_limit_activations_and_aggregations() {
+        let mut rng = thread_rng();
+        let mut g = Genome::new(2, 1);
+        let configuration = Configuration {
+            activation_options: vec![ActivationKind::Tanh, ActivationKind::Relu],
+            aggregation_options: vec![Aggregation::Sum],
+            ..Default::default()
+        };
+        let mut innovations = InnovationTracker::for_genome(&g);
+
+        for _ in 0..20 {
+            mutate(
+                &MutationKind::AddNode,
+                &mut g,
+                &configuration,
+                &mut innovations,
+                &mut rng,
+            );
+            mutate(
+                &MutationKind::ModifyActivation,
+                &mut g,
+                &configuration,
+                &mut innovations,
+                &mut rng,
+            );
+            mutate(
+                &MutationKind::ModifyAggregation,
+                &mut g,
+                &configuration,
+                &mut innovations,
+                &mut rng,
+            );
+        }
+
+        assert!(g
+            .nodes()
+            .iter()
+            .filter(|n| !matches!(n.kind, NodeKind::Input))
+            .all(|n| configuration.activation_options.contains(&n.activation)
+                && n.aggregation == Aggregation::Sum));
+    }
+
     #[test]
     fn mutate_genome() {
         use std::collections::HashMap;
@@ -457,13 +681,20 @@ mod tests {
 
         let mut times: HashMap<MutationKind, Vec<time::Duration>> = HashMap::new();
         let mut g = Genome::new(1, 1);
+        let mut innovations = InnovationTracker::for_genome(&g);
 
         let limit = 50;
         for i in 1..=limit {
             let kind: MutationKind = random();
 
             let before = std::time::Instant::now();
-            mutate(&kind, &mut g);
+            mutate(
+                &kind,
+                &mut g,
+                &Configuration::default(),
+                &mut innovations,
+                &mut thread_rng(),
+            );
             let after = std::time::Instant::now();
             let duration = after.duration_since(before);
 
@@ -505,4 +736,24 @@ mod tests {
             g.connections().iter().filter(|c| !c.disabled).count(),
         );
     }
+
+    #[test]
+    fn same_splits_get_the_same_markings() {
+        let mut innovations = InnovationTracker::new(1, 1);
+        let mut a = Genome::new(1, 1);
+        let mut b = Genome::new(1, 1);
+
+        add_node(&mut a, &mut innovations, &mut thread_rng());
+        add_node(&mut b, &mut innovations, &mut thread_rng());
+
+        assert_eq!(a.nodes()[2].innovation, b.nodes()[2].innovation);
+        assert_eq!(a.connections()[1].innovation, b.connections()[1].innovation);
+        assert_eq!(a.connections()[2].innovation, b.connections()[2].innovation);
+
+        innovations.new_generation();
+        let mut c = Genome::new(1, 1);
+        add_node(&mut c, &mut innovations, &mut thread_rng());
+
+        assert_ne!(c.nodes()[2].innovation, a.nodes()[2].innovation);
+    }
 }
@@ -0,0 +1,360 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use super::configuration::Configuration;
+use super::speciation::GenomeBank;
+use super::{Evaluation, NoveltyArchive, NEAT};
+use crate::genome::{Genome, GenomeId, InnovationTracker};
+use crate::network::Network;
+use crate::rng::NeatRng;
+use crate::speciation::SpeciesSet;
+
+/// Everything needed to continue an evolution run, borrowed from a running system
+#[derive(Serialize)]
+struct CheckpointRef<'a> {
+    inputs: usize,
+    outputs: usize,
+    generation: usize,
+    configuration: &'a Configuration,
+    genomes: &'a GenomeBank,
+    species_set: &'a SpeciesSet,
+    innovations: &'a InnovationTracker,
+    novelty_archive: &'a NoveltyArchive,
+    population_seed: &'a Option<(Vec<Genome>, f64)>,
+    immigrants: &'a [Genome],
+    rng: &'a NeatRng,
+}
+
+/// Everything needed to continue an evolution run, as read from a checkpoint file
+#[derive(Deserialize)]
+struct Checkpoint {
+    inputs: usize,
+    outputs: usize,
+    generation: usize,
+    configuration: Configuration,
+    genomes: GenomeBank,
+    species_set: SpeciesSet,
+    innovations: InnovationTracker,
+    novelty_archive: NoveltyArchive,
+    population_seed: Option<(Vec<Genome>, f64)>,
+    immigrants: Vec<Genome>,
+    rng: NeatRng,
+}
+
+fn to_io_error(error: bincode::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+impl NEAT {
+    /// Writes a checkpoint to the given path after every `every` generations, the file is
+    /// overwritten each time
+    ///
+    /// A checkpoint that can't be written doesn't stop the run, the error is reported in the
+    /// `checkpoint_error` of the generation's summary.
+    pub fn checkpoint_every<P: AsRef<Path>>(&mut self, every: usize, path: P) {
+        assert!(
+            every > 0,
+            "Checkpoints have to be written every 1 or more generations"
+        );
+
+        self.checkpoints = Some((every, path.as_ref().to_path_buf()));
+    }
+
+    /// Writes the current state of the evolution process to a file
+    ///
+    /// The checkpoint is written next to the file first and then moved over it, so an interrupted
+    /// write leaves the previous checkpoint intact.
+    pub fn save_checkpoint<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let checkpoint = CheckpointRef {
+            inputs: self.inputs,
+            outputs: self.outputs,
+            generation: self.generation,
+            configuration: &self.configuration,
+            genomes: &self.genomes,
+            species_set: &self.species_set,
+            innovations: &self.innovations,
+            novelty_archive: &self.novelty_archive,
+            population_seed: &self.population_seed,
+            immigrants: &self.immigrants,
+            rng: &self.rng,
+        };
+
+        let mut temporary_path = path.as_ref().as_os_str().to_owned();
+        temporary_path.push(".tmp");
+
+        let mut writer = BufWriter::new(File::create(&temporary_path)?);
+        bincode::serialize_into(&mut writer, &checkpoint).map_err(to_io_error)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+
+        std::fs::rename(temporary_path, path)
+    }
+
+    /// Restores a system from a checkpoint, `start` then continues from the generation after the
+    /// one that was saved
+    pub fn resume_from<P, F>(path: P, fitness_fn: F) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+        F: Fn(&mut Network) -> f64 + Send + Sync + 'static,
+    {
+        NEAT::resume(path, Evaluation::Individual(Box::new(fitness_fn)))
+    }
+
+    /// Like `resume_from`, for a system created with `with_population_fitness`
+    pub fn resume_with_population_fitness<P, F>(path: P, fitness_fn: F) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+        F: Fn(&mut [(GenomeId, Network)]) -> HashMap<GenomeId, f64> + Send + Sync + 'static,
+    {
+        NEAT::resume(path, Evaluation::Population(Box::new(fitness_fn)))
+    }
+
+    /// Like `resume_from`, for a system created with `with_novelty`
+    pub fn resume_with_novelty<P, F>(path: P, behaviour_fn: F) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+        F: Fn(&mut Network) -> (f64, Vec<f64>) + Send + Sync + 'static,
+    {
+        NEAT::resume(path, Evaluation::Novelty(Box::new(behaviour_fn)))
+    }
+
+    /// Like `resume_from`, for a system created with `with_objectives`
+    pub fn resume_with_objectives<P, F>(path: P, objectives_fn: F) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+        F: Fn(&mut Network) -> Vec<f64> + Send + Sync + 'static,
+    {
+        NEAT::resume(path, Evaluation::MultiObjective(Box::new(objectives_fn)))
+    }
+
+    fn resume<P: AsRef<Path>>(path: P, evaluation: Evaluation) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let checkpoint: Checkpoint = bincode::deserialize_from(reader).map_err(to_io_error)?;
+
+        let mut system = NEAT::with_evaluation(checkpoint.inputs, checkpoint.outputs, evaluation);
+
+        system.set_configuration(checkpoint.configuration);
+        system.genomes = checkpoint.genomes;
+        system
+            .genomes
+            .set_configuration(system.configuration.clone());
+        system.species_set = checkpoint.species_set;
+        system
+            .species_set
+            .set_configuration(system.configuration.clone());
+        system.innovations = checkpoint.innovations;
+        system.novelty_archive = checkpoint.novelty_archive;
+        system.population_seed = checkpoint.population_seed;
+        system.immigrants = checkpoint.immigrants;
+        system.rng = checkpoint.rng;
+        system.generation = checkpoint.generation;
+
+        Ok(system)
+    }
+
+    pub(super) fn checkpoint_if_due(&self) -> io::Result<()> {
+        match &self.checkpoints {
+            Some((every, path)) if self.generation.is_multiple_of(*every) => {
+                self.save_checkpoint(path)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genome::Genome;
+    use crate::neat::tests::first_input_to_one as fitness;
+
+    fn configuration(max_generations: usize) -> Configuration {
+        Configuration {
+            population_size: 30,
+            max_generations,
+            seed: Some(1337),
+            ..Default::default()
+        }
+    }
+
+    fn population(system: &NEAT) -> Vec<(Genome, u64)> {
+        system
+            .genomes
+            .genomes()
+            .iter()
+            .map(|(genome_id, genome)| {
+                let fitness = system.genomes.fitnesses().get(genome_id).unwrap();
+
+                (genome.clone(), fitness.to_bits())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resumed_run_matches_uninterrupted_run() {
+        let filename = "resume_test_checkpoint.bin";
+
+        let mut uninterrupted = NEAT::new(2, 1, fitness);
+        uninterrupted.set_configuration(configuration(6));
+        uninterrupted.start();
+
+        let mut interrupted = NEAT::new(2, 1, fitness);
+        interrupted.set_configuration(configuration(3));
+        interrupted.checkpoint_every(3, filename);
+        interrupted.start();
+
+        let mut resumed = NEAT::resume_from(filename, fitness).unwrap();
+        resumed.set_configuration(configuration(6));
+        resumed.start();
+
+        std::fs::remove_file(filename).unwrap();
+
+        assert_eq!(population(&uninterrupted), population(&resumed));
+    }
+
+    /// Output for `[1., 0.]` relative to the mean output of the generation
+    fn relative_output(ids_and_networks: &mut [(GenomeId, Network)]) -> HashMap<GenomeId, f64> {
+        let outputs: Vec<(GenomeId, f64)> = ids_and_networks
+            .iter_mut()
+            .map(|(genome_id, network)| (*genome_id, network.forward_pass(&vec![1., 0.])[0]))
+            .collect();
+        let mean = outputs.iter().map(|(_, output)| output).sum::<f64>() / outputs.len() as f64;
+
+        outputs
+            .into_iter()
+            .map(|(genome_id, output)| (genome_id, output - mean))
+            .collect()
+    }
+
+    #[test]
+    fn resumed_population_fitness_run_matches_uninterrupted_run() {
+        let filename = "resume_population_test_checkpoint.bin";
+
+        let mut uninterrupted = NEAT::with_population_fitness(2, 1, relative_output);
+        uninterrupted.set_configuration(configuration(6));
+        uninterrupted.start();
+
+        let mut interrupted = NEAT::with_population_fitness(2, 1, relative_output);
+        interrupted.set_configuration(configuration(3));
+        interrupted.checkpoint_every(3, filename);
+        interrupted.start();
+
+        let mut resumed = NEAT::resume_with_population_fitness(filename, relative_output).unwrap();
+        resumed.set_configuration(configuration(6));
+        resumed.start();
+
+        std::fs::remove_file(filename).unwrap();
+
+        assert_eq!(population(&uninterrupted), population(&resumed));
+    }
+
+    /// Fitness and behaviour are the outputs for two different inputs
+    fn outputs(n: &mut Network) -> (f64, Vec<f64>) {
+        let first = n.forward_pass(&vec![1., 0.])[0];
+        let second = n.forward_pass(&vec![0., 1.])[0];
+
+        (first, vec![first, second])
+    }
+
+    #[test]
+    fn resumed_novelty_run_matches_uninterrupted_run() {
+        let filename = "resume_novelty_test_checkpoint.bin";
+        let configuration = |max_generations| Configuration {
+            novelty_threshold: 0.05,
+            ..configuration(max_generations)
+        };
+
+        let mut uninterrupted = NEAT::with_novelty(2, 1, outputs);
+        uninterrupted.set_configuration(configuration(6));
+        uninterrupted.start();
+
+        let mut interrupted = NEAT::with_novelty(2, 1, outputs);
+        interrupted.set_configuration(configuration(3));
+        interrupted.checkpoint_every(3, filename);
+        interrupted.start();
+        assert!(!interrupted.novelty_archive().behaviours().is_empty());
+
+        let mut resumed = NEAT::resume_with_novelty(filename, outputs).unwrap();
+        resumed.set_configuration(configuration(6));
+        resumed.start();
+
+        std::fs::remove_file(filename).unwrap();
+
+        assert_eq!(population(&uninterrupted), population(&resumed));
+        assert_eq!(
+            uninterrupted.novelty_archive().behaviours(),
+            resumed.novelty_archive().behaviours()
+        );
+    }
+
+    #[test]
+    fn resumed_run_keeps_seeds_and_immigrants() {
+        let filename = "resume_seeds_test_checkpoint.bin";
+        let mut rng = crate::rng::from_seed(Some(5));
+        let seed = Genome::new_with_rng(2, 1, &mut rng);
+        let mut immigrant = Genome::new_with_rng(2, 1, &mut rng);
+        immigrant.add_node(&mut rng);
+
+        let mut system = NEAT::new(2, 1, fitness);
+        system.set_configuration(configuration(6));
+        system.seed_population(vec![seed], 0.5);
+        system.initialize();
+        (0..3).for_each(|_| {
+            system.step_generation();
+        });
+        system.immigrants.push(immigrant);
+
+        system.save_checkpoint(filename).unwrap();
+        let mut resumed = NEAT::resume_from(filename, fitness).unwrap();
+        std::fs::remove_file(filename).unwrap();
+
+        assert_eq!(resumed.immigrants, system.immigrants);
+        (0..3).for_each(|_| {
+            system.step_generation();
+            resumed.step_generation();
+        });
+        assert_eq!(population(&system), population(&resumed));
+
+        // Both start over from the same seeds
+        system.initialize();
+        resumed.initialize();
+        assert_eq!(population(&system), population(&resumed));
+    }
+
+    #[test]
+    fn checkpoints_replace_the_previous_one() {
+        let filename = "replace_test_checkpoint.bin";
+        std::fs::write(filename, b"previous").unwrap();
+
+        let mut system = NEAT::new(2, 1, fitness);
+        system.set_configuration(configuration(1));
+        system.initialize();
+        system.save_checkpoint(filename).unwrap();
+
+        let resumed = NEAT::resume_from(filename, fitness).unwrap();
+        std::fs::remove_file(filename).unwrap();
+
+        assert_eq!(population(&system), population(&resumed));
+        assert!(!Path::new("replace_test_checkpoint.bin.tmp").exists());
+    }
+
+    #[test]
+    fn failed_checkpoints_are_reported() {
+        let mut system = NEAT::new(2, 1, fitness);
+        system.set_configuration(configuration(2));
+        system.checkpoint_every(2, "missing_directory/checkpoint.bin");
+        system.initialize();
+
+        assert_eq!(system.step_generation().checkpoint_error, None);
+        assert!(system.step_generation().checkpoint_error.is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "Checkpoints have to be written every 1 or more generations")]
+    fn checkpoints_need_an_interval() {
+        NEAT::new(2, 1, fitness).checkpoint_every(0, "checkpoint.bin");
+    }
+}
@@ -1,9 +1,23 @@
 use std::default::Default;
+#[cfg(feature = "config-file")]
+use std::fs::read_to_string;
+#[cfg(feature = "config-file")]
+use std::io;
+#[cfg(feature = "config-file")]
+use std::path::Path;
 
+use crate::activation::ActivationKind;
+use crate::aggregations::Aggregation;
+use crate::genome::InitialTopology;
 use crate::mutations::MutationKind;
 
 /// Holds configuration options of the whole NEAT process
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    any(feature = "checkpoint", feature = "config-file"),
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
 pub struct Configuration {
     /// The generations limit of for the evolution process
     pub max_generations: usize,
@@ -14,6 +28,10 @@ pub struct Configuration {
     /// The ratio of champion individuals that are copied to the next generation
     pub elitism: f64,
 
+    /// The number of champions of every species that are copied to the next generation, on top
+    /// of the ratio, as neat-python's elitism
+    pub elites_per_species: usize,
+
     /// The minimum amount of species that need to exist after the removal of stagnated ones
     pub elitism_species: usize,
 
@@ -32,12 +50,52 @@ pub struct Configuration {
     /// The ratio of genomes that will survive to the next generation
     pub survival_ratio: f64,
 
+    /// How the genomes of the initial population are connected
+    pub initial_topology: InitialTopology,
+
+    /// Activation functions nodes can have, any of them when empty
+    pub activation_options: Vec<ActivationKind>,
+
+    /// Aggregation functions nodes can have, any of them when empty
+    pub aggregation_options: Vec<Aggregation>,
+
     /// The types of mutations available and their sampling weights
+    #[cfg_attr(
+        any(feature = "checkpoint", feature = "config-file"),
+        serde(with = "mutation_weights")
+    )]
     pub mutation_kinds: Vec<(MutationKind, usize)>,
 
     /// The process will stop if the fitness goal is reached
     pub fitness_goal: Option<f64>,
 
+    /// Replaces NaN or infinite fitness, should be lower than any real fitness
+    pub non_finite_fitness: f64,
+
+    /// Allows cycles and self connections, networks then keep their state between forward passes
+    pub recurrent_connections: bool,
+
+    /// Seeds every random decision, runs with the same seed and configuration are identical
+    pub seed: Option<u64>,
+
+    /*
+     * Novelty search, only used by systems created with `NEAT::with_novelty`
+     */
+    /// How many of the nearest behaviours the novelty of a behaviour is measured against
+    pub novelty_neighbours: usize,
+
+    /// Behaviours with a novelty above this are added to the archive
+    pub novelty_threshold: f64,
+
+    /// Share of novelty in the selection score, the rest is fitness
+    pub novelty_weight: f64,
+
+    /*
+     * MAP-Elites, only used by `MapElites`
+     */
+    /// Lowest fitness the QD-score counts from, elites below it add nothing
+    pub qd_score_min_fitness: f64,
+
     /*
      * Genomic distance during speciation
      */
@@ -50,6 +108,7 @@ pub struct Configuration {
     pub distance_node_bias_coefficient: f64,
     pub distance_node_activation_coefficient: f64,
     pub distance_node_aggregation_coefficient: f64,
+    pub distance_node_time_constant_coefficient: f64,
 
     /// A limit on how distant two genomes can be to belong to the same species
     pub compatibility_threshold: f64,
@@ -61,25 +120,207 @@ impl Default for Configuration {
             max_generations: 1000,
             population_size: 150,
             elitism: 0.1,
+            elites_per_species: 0,
             elitism_species: 3,
             stagnation_after: 50,
             node_cost: 0.,
             connection_cost: 0.,
             mutation_rate: 0.5,
             survival_ratio: 0.5,
+            initial_topology: InitialTopology::Full,
+            activation_options: vec![],
+            aggregation_options: vec![],
             mutation_kinds: default_mutation_kinds(),
             fitness_goal: None,
+            non_finite_fitness: 0.,
+            recurrent_connections: false,
+            seed: None,
+            novelty_neighbours: 15,
+            novelty_threshold: 1.,
+            novelty_weight: 1.,
+            qd_score_min_fitness: 0.,
             distance_connection_disjoint_coefficient: 1.,
             distance_connection_weight_coeficcient: 0.5,
             distance_connection_disabled_coefficient: 0.5,
             distance_node_bias_coefficient: 0.33,
             distance_node_activation_coefficient: 0.33,
             distance_node_aggregation_coefficient: 0.33,
+            distance_node_time_constant_coefficient: 0.33,
             compatibility_threshold: 3.,
         }
     }
 }
 
+impl Configuration {
+    /// Lists every option, or combination of options, the evolution can't work with
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = vec![];
+
+        if self.population_size == 0 {
+            problems.push("population_size has to be at least 1".to_string());
+        }
+        if !is_fraction(self.survival_ratio) {
+            problems.push(format!(
+                "survival_ratio has to be in (0, 1], got {}",
+                self.survival_ratio
+            ));
+        }
+        if !(0. ..=1.).contains(&self.elitism) {
+            problems.push(format!("elitism has to be in [0, 1], got {}", self.elitism));
+        }
+        if !(0. ..=1.).contains(&self.mutation_rate) {
+            problems.push(format!(
+                "mutation_rate has to be in [0, 1], got {}",
+                self.mutation_rate
+            ));
+        }
+        if self.elitism_species > self.population_size {
+            problems.push(format!(
+                "elitism_species ({}) can't be larger than population_size ({})",
+                self.elitism_species, self.population_size
+            ));
+        }
+        if self.stagnation_after == 0 {
+            problems.push("stagnation_after has to be at least 1".to_string());
+        }
+        if self.activation_options.contains(&ActivationKind::Input) {
+            problems.push("activation_options can't contain Input".to_string());
+        }
+        if self.mutation_kinds.iter().all(|(_, weight)| *weight == 0) {
+            problems
+                .push("mutation_kinds needs at least one kind with a weight above 0".to_string());
+        }
+        if let InitialTopology::Fraction(fraction) = self.initial_topology {
+            if !is_fraction(fraction) {
+                problems.push(format!(
+                    "initial_topology fraction has to be in (0, 1], got {}",
+                    fraction
+                ));
+            }
+        }
+        if self.compatibility_threshold.is_nan() || self.compatibility_threshold <= 0. {
+            problems.push(format!(
+                "compatibility_threshold has to be above 0, got {}",
+                self.compatibility_threshold
+            ));
+        }
+        if !self.non_finite_fitness.is_finite() {
+            problems.push("non_finite_fitness has to be finite".to_string());
+        }
+        if self.novelty_neighbours == 0 {
+            problems.push("novelty_neighbours has to be at least 1".to_string());
+        }
+        if !self.novelty_threshold.is_finite() || self.novelty_threshold < 0. {
+            problems.push(format!(
+                "novelty_threshold has to be finite and at least 0, got {}",
+                self.novelty_threshold
+            ));
+        }
+        if !(0. ..=1.).contains(&self.novelty_weight) {
+            problems.push(format!(
+                "novelty_weight has to be in [0, 1], got {}",
+                self.novelty_weight
+            ));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+}
+
+/// In (0, 1]
+fn is_fraction(x: f64) -> bool {
+    x > 0. && x <= 1.
+}
+
+/// Options that are left out keep their default values, the result is validated
+#[cfg(feature = "config-file")]
+impl Configuration {
+    pub fn from_toml(toml: &str) -> io::Result<Self> {
+        let configuration = toml::from_str(toml).map_err(invalid_data)?;
+        Configuration::validated(configuration)
+    }
+
+    pub fn from_json(json: &str) -> io::Result<Self> {
+        let configuration = serde_json::from_str(json).map_err(invalid_data)?;
+        Configuration::validated(configuration)
+    }
+
+    pub fn from_toml_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Configuration::from_toml(&read_to_string(path)?)
+    }
+
+    pub fn from_json_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Configuration::from_json(&read_to_string(path)?)
+    }
+
+    fn validated(configuration: Configuration) -> io::Result<Self> {
+        configuration
+            .validate()
+            .map_err(|problems| invalid_data(problems.join(", ")))?;
+
+        Ok(configuration)
+    }
+}
+
+#[cfg(feature = "config-file")]
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Mutation kinds are written as a table of names and weights, like `AddNode = 10`
+#[cfg(any(feature = "checkpoint", feature = "config-file"))]
+mod mutation_weights {
+    use serde::de::value::StrDeserializer;
+    use serde::de::{IntoDeserializer, MapAccess, Visitor};
+    use serde::ser::SerializeMap;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::fmt;
+
+    use crate::mutations::MutationKind;
+
+    pub fn serialize<S: Serializer>(
+        kinds: &[(MutationKind, usize)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(kinds.len()))?;
+        // Keys are plain names, TOML can't use enums as keys
+        for (kind, weight) in kinds {
+            map.serialize_entry(&format!("{:?}", kind), weight)?;
+        }
+        map.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<(MutationKind, usize)>, D::Error> {
+        struct WeightsVisitor;
+
+        impl<'de> Visitor<'de> for WeightsVisitor {
+            type Value = Vec<(MutationKind, usize)>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a map of mutation kinds to weights")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut kinds = vec![];
+                while let Some((name, weight)) = map.next_entry::<String, usize>()? {
+                    let name: StrDeserializer<A::Error> = name.as_str().into_deserializer();
+                    kinds.push((MutationKind::deserialize(name)?, weight));
+                }
+
+                Ok(kinds)
+            }
+        }
+
+        deserializer.deserialize_map(WeightsVisitor)
+    }
+}
+
 pub fn default_mutation_kinds() -> Vec<(MutationKind, usize)> {
     use MutationKind::*;
 
@@ -94,3 +335,73 @@ pub fn default_mutation_kinds() -> Vec<(MutationKind, usize)> {
         (ModifyAggregation, 10),
     ]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_configuration_is_valid() {
+        assert_eq!(Configuration::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn invalid_combinations_are_reported() {
+        let configuration = Configuration {
+            survival_ratio: 0.,
+            elitism_species: 200,
+            mutation_kinds: vec![(MutationKind::AddNode, 0)],
+            novelty_weight: 2.,
+            ..Default::default()
+        };
+
+        assert_eq!(configuration.validate().unwrap_err().len(), 4);
+    }
+
+    #[cfg(feature = "config-file")]
+    #[test]
+    fn from_toml() {
+        let configuration = Configuration::from_toml(
+            r#"
+            population_size = 50
+            fitness_goal = 0.9
+            initial_topology = { Fraction = 0.5 }
+
+            [mutation_kinds]
+            AddNode = 3
+            ModifyWeight = 20
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(configuration.population_size, 50);
+        assert_eq!(configuration.fitness_goal, Some(0.9));
+        assert_eq!(
+            configuration.initial_topology,
+            InitialTopology::Fraction(0.5)
+        );
+        assert_eq!(
+            configuration.mutation_kinds,
+            vec![(MutationKind::AddNode, 3), (MutationKind::ModifyWeight, 20)]
+        );
+        assert_eq!(configuration.elitism, Configuration::default().elitism);
+    }
+
+    #[cfg(feature = "config-file")]
+    #[test]
+    fn from_json() {
+        let configuration = Configuration::from_json(
+            r#"{ "population_size": 50, "mutation_kinds": { "AddConnection": 1 } }"#,
+        )
+        .unwrap();
+
+        assert_eq!(configuration.population_size, 50);
+        assert_eq!(
+            configuration.mutation_kinds,
+            vec![(MutationKind::AddConnection, 1)]
+        );
+
+        assert!(Configuration::from_json(r#"{ "survival_ratio": 2 }"#).is_err());
+        assert!(Configuration::from_json(r#"{ "mutation_kinds": { "Teleport": 1 } }"#).is_err());
+    }
+}
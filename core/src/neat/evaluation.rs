@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use crate::genome::GenomeId;
+use crate::network::Network;
+
+/// A fitness function that scores every network in isolation, it can carry its own state and is
+/// shared between rayon workers
+pub type FitnessFn = dyn Fn(&mut Network) -> f64 + Send + Sync;
+
+/// A fitness function that receives the whole generation at once, useful for tournaments, self-play
+/// or fitness relative to the rest of the population
+pub type PopulationFitnessFn =
+    dyn Fn(&mut [(GenomeId, Network)]) -> HashMap<GenomeId, f64> + Send + Sync;
+
+/// A function for novelty search, returns the fitness of the network and a description of how it
+/// behaved, like the final position in a maze
+pub type BehaviourFn = dyn Fn(&mut Network) -> (f64, Vec<f64>) + Send + Sync;
+
+/// A function for multi-objective evolution, returns every objective of the network, higher is
+/// better for each of them
+pub type ObjectivesFn = dyn Fn(&mut Network) -> Vec<f64> + Send + Sync;
+
+/// The way genomes of a generation get their fitness
+pub enum Evaluation {
+    Individual(Box<FitnessFn>),
+    Population(Box<PopulationFitnessFn>),
+    Novelty(Box<BehaviourFn>),
+    MultiObjective(Box<ObjectivesFn>),
+}
+
+/// What selection uses besides the fitness, one entry for every network
+#[derive(Debug, PartialEq)]
+pub enum Descriptors {
+    None,
+    Behaviours(Vec<Vec<f64>>),
+    Objectives(Vec<Vec<f64>>),
+}
+
+impl Evaluation {
+    /// Returns the fitness of every network, and what else selection needs to know about it, in
+    /// the same order as the networks were given
+    pub fn evaluate(
+        &self,
+        ids_and_networks: &mut [(GenomeId, Network)],
+    ) -> (Vec<f64>, Descriptors) {
+        use rayon::prelude::*;
+
+        match self {
+            Evaluation::Individual(fitness_fn) => {
+                let fitnesses = ids_and_networks
+                    .par_iter_mut()
+                    .map(|(_, network)| (fitness_fn)(network))
+                    .collect();
+
+                (fitnesses, Descriptors::None)
+            }
+            Evaluation::Population(fitness_fn) => {
+                let fitnesses = (fitness_fn)(ids_and_networks);
+
+                let fitnesses = ids_and_networks
+                    .iter()
+                    .map(|(genome_id, _)| {
+                        *fitnesses
+                            .get(genome_id)
+                            .expect("Population fitness function has to score every genome")
+                    })
+                    .collect();
+
+                (fitnesses, Descriptors::None)
+            }
+            Evaluation::Novelty(behaviour_fn) => {
+                let (fitnesses, behaviours) = ids_and_networks
+                    .par_iter_mut()
+                    .map(|(_, network)| (behaviour_fn)(network))
+                    .unzip();
+
+                (fitnesses, Descriptors::Behaviours(behaviours))
+            }
+            Evaluation::MultiObjective(objectives_fn) => {
+                let objectives: Vec<Vec<f64>> = ids_and_networks
+                    .par_iter_mut()
+                    .map(|(_, network)| (objectives_fn)(network))
+                    .collect();
+
+                // The first objective is reported as the fitness
+                let fitnesses = objectives
+                    .iter()
+                    .map(|objectives| {
+                        *objectives
+                            .first()
+                            .expect("Objectives function has to return at least one objective")
+                    })
+                    .collect();
+
+                (fitnesses, Descriptors::Objectives(objectives))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genome::Genome;
+
+    fn ids_and_networks(count: usize) -> Vec<(GenomeId, Network)> {
+        (0..count)
+            .map(|_| {
+                let genome = Genome::new(1, 1);
+                (genome.id(), Network::from(&genome))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn individual_fitness_keeps_order() {
+        let mut networks = ids_and_networks(5);
+        let evaluation = Evaluation::Individual(Box::new(|n| n.nodes.len() as f64));
+
+        let (fitnesses, behaviours) = evaluation.evaluate(&mut networks);
+
+        assert_eq!(fitnesses, vec![2.; 5]);
+        assert_eq!(behaviours, Descriptors::None);
+    }
+
+    #[test]
+    fn population_fitness_sees_every_genome() {
+        let mut networks = ids_and_networks(5);
+        let evaluation = Evaluation::Population(Box::new(|population| {
+            let population_size = population.len() as f64;
+
+            population
+                .iter()
+                .enumerate()
+                .map(|(rank, (genome_id, _))| (*genome_id, rank as f64 / population_size))
+                .collect()
+        }));
+
+        let (fitnesses, _) = evaluation.evaluate(&mut networks);
+
+        assert_eq!(fitnesses, vec![0., 0.2, 0.4, 0.6, 0.8]);
+    }
+
+    #[test]
+    fn novelty_returns_behaviours() {
+        let mut networks = ids_and_networks(3);
+        let evaluation = Evaluation::Novelty(Box::new(|n| (1., vec![n.connections.len() as f64])));
+
+        let (fitnesses, behaviours) = evaluation.evaluate(&mut networks);
+
+        assert_eq!(fitnesses, vec![1.; 3]);
+        assert_eq!(behaviours, Descriptors::Behaviours(vec![vec![1.]; 3]));
+    }
+
+    #[test]
+    fn first_objective_is_the_fitness() {
+        let mut networks = ids_and_networks(3);
+        let evaluation = Evaluation::MultiObjective(Box::new(|n| vec![2., n.nodes.len() as f64]));
+
+        let (fitnesses, objectives) = evaluation.evaluate(&mut networks);
+
+        assert_eq!(fitnesses, vec![2.; 3]);
+        assert_eq!(objectives, Descriptors::Objectives(vec![vec![2., 2.]; 3]));
+    }
+}
@@ -0,0 +1,449 @@
+use rand::Rng;
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+use super::{GenerationSummary, NEAT};
+use crate::genome::{Genome, GenomeId};
+use crate::network::Network;
+use crate::rng::{self, NeatRng};
+
+/// Which island the migrants of an island go to
+#[derive(Debug, Clone, PartialEq)]
+pub enum MigrationTopology {
+    /// Every island sends to the next one, the last one to the first
+    Ring,
+    /// Every island sends to a random other island
+    Random,
+}
+
+/// When and how genomes move between islands
+#[derive(Debug, Clone, PartialEq)]
+pub struct Migration {
+    /// Migration happens after every `every` generations
+    pub every: usize,
+    /// How many of the fittest genomes of an island are copied to another one
+    pub migrants: usize,
+    pub topology: MigrationTopology,
+    /// Seeds the random topology
+    pub seed: Option<u64>,
+}
+
+impl Default for Migration {
+    fn default() -> Self {
+        Migration {
+            every: 10,
+            migrants: 2,
+            topology: MigrationTopology::Ring,
+            seed: None,
+        }
+    }
+}
+
+/// Evolves several independent populations side by side, copies of the fittest genomes of one
+/// now and then join the next generation of another
+///
+/// Every island is a `NEAT` system with its own configuration and fitness function, their
+/// generations run in parallel.
+pub struct Islands {
+    islands: Vec<NEAT>,
+    migration: Migration,
+    rng: NeatRng,
+    generation: usize,
+}
+
+impl Islands {
+    pub fn new(islands: Vec<NEAT>, migration: Migration) -> Self {
+        assert!(!islands.is_empty(), "At least one island is needed");
+        assert!(
+            migration.every > 0,
+            "Migration has to happen every 1 or more generations"
+        );
+        assert!(
+            islands
+                .iter()
+                .all(|island| island.inputs == islands[0].inputs
+                    && island.outputs == islands[0].outputs),
+            "Every island needs the same number of inputs and outputs"
+        );
+
+        Islands {
+            islands,
+            rng: rng::from_seed(migration.seed),
+            migration,
+            generation: 0,
+        }
+    }
+
+    pub fn islands(&self) -> &[NEAT] {
+        &self.islands
+    }
+
+    /// Number of the last completed generation, 0 right after initialization
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// Creates and evaluates the initial population of every island
+    pub fn initialize(&mut self) {
+        self.islands
+            .par_iter_mut()
+            .for_each(|island| island.initialize());
+
+        self.rng = rng::from_seed(self.migration.seed);
+        self.generation = 0;
+    }
+
+    /// Runs a generation on every island that hasn't reached its `max_generations` yet and
+    /// migrates if it's time to, islands that are done report their last generation again
+    pub fn step_generation(&mut self) -> Vec<GenerationSummary> {
+        let summaries = self
+            .islands
+            .par_iter_mut()
+            .map(|island| {
+                if is_running(island) {
+                    island.step_generation()
+                } else {
+                    island.summary()
+                }
+            })
+            .collect();
+
+        self.generation += 1;
+        if self.generation.is_multiple_of(self.migration.every) {
+            self.migrate();
+        }
+
+        summaries
+    }
+
+    /// Runs generations until an island reaches its fitness goal or every island its own
+    /// generation limit, returns the fittest network of all islands
+    pub fn start(&mut self) -> (Network, f64) {
+        if self.islands.iter().any(|island| island.best().is_none()) {
+            self.initialize();
+        }
+
+        while self.islands.iter().any(is_running) {
+            let summaries = self.step_generation();
+
+            if summaries.iter().any(|summary| summary.goal_reached) {
+                break;
+            }
+        }
+
+        self.best().unwrap()
+    }
+
+    /// The fittest network of all islands and its fitness, `None` before initialization
+    pub fn best(&self) -> Option<(Network, f64)> {
+        self.islands
+            .iter()
+            .filter_map(|island| island.best())
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// Islands that reached their generation limit neither send nor receive migrants
+    fn migrate(&mut self) {
+        let running: Vec<usize> = (0..self.islands.len())
+            .filter(|index| is_running(&self.islands[*index]))
+            .collect();
+        if running.len() < 2 || self.migration.migrants == 0 {
+            return;
+        }
+
+        // Migrants arrive grouped by the island they come from
+        let mut incoming: Vec<Vec<Vec<Genome>>> = vec![vec![]; self.islands.len()];
+
+        for (position, &source) in running.iter().enumerate() {
+            let target = match self.migration.topology {
+                MigrationTopology::Ring => running[(position + 1) % running.len()],
+                MigrationTopology::Random => {
+                    let other = self.rng.gen::<usize>() % (running.len() - 1);
+
+                    if other >= position {
+                        running[other + 1]
+                    } else {
+                        running[other]
+                    }
+                }
+            };
+
+            let emigrants = self.islands[source].emigrants(self.migration.migrants);
+            incoming[target].push(emigrants);
+        }
+
+        self.islands
+            .par_iter_mut()
+            .zip(incoming)
+            .for_each(|(island, immigrants)| island.immigrate(immigrants));
+    }
+}
+
+fn is_running(island: &NEAT) -> bool {
+    island.generation() < island.configuration().max_generations
+}
+
+impl NEAT {
+    /// Copies of the fittest genomes
+    fn emigrants(&self, count: usize) -> Vec<Genome> {
+        let mut ids_and_fitnesses: Vec<(&GenomeId, &f64)> =
+            self.genomes.fitnesses().iter().collect();
+        ids_and_fitnesses.sort_by(|a, b| b.1.total_cmp(a.1));
+
+        ids_and_fitnesses
+            .into_iter()
+            .take(count)
+            .map(|(genome_id, _)| self.genomes.genomes().get(genome_id).unwrap().clone())
+            .collect()
+    }
+
+    /// Queues the immigrants for the next generation, every group comes from another island
+    fn immigrate(&mut self, immigrants: Vec<Vec<Genome>>) {
+        // Islands mark their genes independently, immigrants get markings of this island
+        for group in immigrants {
+            let mut nodes = HashMap::new();
+
+            for immigrant in group {
+                let mut genome = immigrant.copy_with_new_id(&mut self.rng);
+                self.innovations.adopt(&mut genome, &mut nodes);
+
+                self.immigrants.push(genome);
+            }
+        }
+    }
+
+    /// Immigrants take the places of random children of the new generation, elites are kept
+    ///
+    /// They are evaluated together with the rest of the generation, so population fitness and
+    /// novelty see them like any other genome.
+    pub(super) fn settle_immigrants(&mut self, offspring: &mut [Genome]) {
+        if self.immigrants.is_empty() {
+            return;
+        }
+
+        // Elites keep the id they had in the previous generation
+        let mut children: Vec<usize> = offspring
+            .iter()
+            .enumerate()
+            .filter(|(_, genome)| !self.genomes.genomes().contains_key(&genome.id()))
+            .map(|(index, _)| index)
+            .collect();
+
+        for immigrant in std::mem::take(&mut self.immigrants) {
+            if children.is_empty() {
+                break;
+            }
+
+            let index = children.swap_remove(self.rng.gen::<usize>() % children.len());
+            offspring[index] = immigrant;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::neat::tests::first_input_to_one;
+    use crate::neat::Configuration;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn island(seed: u64, compatibility_threshold: f64) -> NEAT {
+        let mut system = NEAT::new(2, 1, first_input_to_one);
+
+        system.set_configuration(Configuration {
+            population_size: 20,
+            max_generations: 6,
+            compatibility_threshold,
+            seed: Some(seed),
+            ..Default::default()
+        });
+
+        system
+    }
+
+    fn islands(topology: MigrationTopology) -> Islands {
+        Islands::new(
+            vec![island(1, 3.), island(2, 2.), island(3, 1.)],
+            Migration {
+                every: 2,
+                migrants: 3,
+                topology,
+                seed: Some(4),
+            },
+        )
+    }
+
+    /// Connections with their weights, these survive the new markings of migration
+    fn genes(genome: &Genome) -> Vec<(usize, usize, u64)> {
+        genome
+            .connections()
+            .iter()
+            .map(|c| (c.from, c.to, c.weight.to_bits()))
+            .collect()
+    }
+
+    #[test]
+    fn migrants_join_the_next_generation() {
+        let mut islands = islands(MigrationTopology::Ring);
+        islands.initialize();
+        islands.step_generation();
+
+        let emigrants: Vec<Vec<_>> = islands
+            .islands()
+            .iter()
+            .map(|island| island.emigrants(3).iter().map(genes).collect())
+            .collect();
+        let fitnesses: Vec<_> = islands
+            .islands()
+            .iter()
+            .map(|island| island.genomes.fitnesses().clone())
+            .collect();
+
+        // Migration itself changes and evaluates nothing
+        islands.migrate();
+        for (island, fitnesses) in islands.islands().iter().zip(&fitnesses) {
+            assert_eq!(island.genomes.fitnesses(), fitnesses);
+        }
+
+        islands.islands.iter_mut().for_each(|island| {
+            island.step_generation();
+        });
+
+        for (index, island) in islands.islands().iter().enumerate() {
+            // The ring brings the fittest genomes of the previous island
+            for emigrant in &emigrants[(index + 2) % 3] {
+                assert!(island
+                    .genomes
+                    .genomes()
+                    .values()
+                    .any(|genome| genes(genome) == *emigrant));
+            }
+        }
+    }
+
+    #[test]
+    fn immigrants_are_evaluated_once() {
+        let evaluations = Arc::new(AtomicUsize::new(0));
+
+        let novelty_island = |seed| {
+            let evaluations = evaluations.clone();
+            let mut system = NEAT::with_novelty(2, 1, move |n| {
+                evaluations.fetch_add(1, Ordering::Relaxed);
+                let output = n.forward_pass(&vec![1., 0.])[0];
+
+                (output, vec![output])
+            });
+
+            system.set_configuration(Configuration {
+                population_size: 20,
+                novelty_threshold: 0.01,
+                seed: Some(seed),
+                ..Default::default()
+            });
+
+            system
+        };
+
+        let mut islands = Islands::new(
+            vec![novelty_island(1), novelty_island(2)],
+            Migration {
+                every: 100,
+                migrants: 3,
+                ..Default::default()
+            },
+        );
+        islands.initialize();
+        islands.step_generation();
+
+        let archive_sizes = |islands: &Islands| -> Vec<usize> {
+            islands
+                .islands()
+                .iter()
+                .map(|island| island.novelty_archive().behaviours().len())
+                .collect()
+        };
+        let evaluations_before = evaluations.load(Ordering::Relaxed);
+        let archive_sizes_before = archive_sizes(&islands);
+
+        islands.migrate();
+
+        assert_eq!(evaluations.load(Ordering::Relaxed), evaluations_before);
+        assert_eq!(archive_sizes(&islands), archive_sizes_before);
+
+        // The next generation, immigrants included, is scored once
+        islands.step_generation();
+        let population: usize = islands
+            .islands()
+            .iter()
+            .map(|island| island.genomes.genomes().len())
+            .sum();
+
+        assert_eq!(
+            evaluations.load(Ordering::Relaxed),
+            evaluations_before + population
+        );
+    }
+
+    #[test]
+    fn islands_stop_at_their_own_limit() {
+        let mut short = island(1, 3.);
+        short.update_configuration(|config| config.max_generations = 2);
+
+        let mut islands = Islands::new(vec![short, island(2, 3.)], Migration::default());
+        islands.start();
+
+        assert_eq!(islands.generation(), 6);
+        assert_eq!(islands.islands()[0].generation(), 2);
+        assert_eq!(islands.islands()[1].generation(), 6);
+    }
+
+    #[test]
+    fn finished_islands_are_left_out_of_migration() {
+        let mut short = island(1, 3.);
+        short.update_configuration(|config| config.max_generations = 2);
+
+        let mut islands = Islands::new(
+            vec![short, island(2, 3.), island(3, 3.)],
+            Migration {
+                every: 1,
+                migrants: 3,
+                ..Default::default()
+            },
+        );
+        islands.initialize();
+
+        for _ in 0..4 {
+            islands.step_generation();
+        }
+
+        // The ring skips the finished island, the others only trade with each other
+        assert!(islands.islands()[0].immigrants.is_empty());
+        assert_eq!(islands.islands()[1].immigrants.len(), 3);
+        assert_eq!(islands.islands()[2].immigrants.len(), 3);
+    }
+
+    #[test]
+    fn seeded_runs_are_identical() {
+        let run = |topology: MigrationTopology| {
+            let mut islands = islands(topology);
+            let (_, fitness) = islands.start();
+
+            assert_eq!(islands.generation(), 6);
+
+            let genomes: Vec<Vec<Genome>> = islands
+                .islands()
+                .iter()
+                .map(|island| island.genomes.genomes().values().cloned().collect())
+                .collect();
+
+            (fitness, genomes)
+        };
+
+        assert_eq!(run(MigrationTopology::Ring), run(MigrationTopology::Ring));
+        assert_eq!(
+            run(MigrationTopology::Random),
+            run(MigrationTopology::Random)
+        );
+    }
+}
@@ -1,302 +1,622 @@
-use rand::random;
+use rand::Rng;
 use rayon::prelude::*;
-use std::cell::RefCell;
-use std::rc::Rc;
-use uuid::Uuid;
+use std::collections::HashMap;
+use std::sync::Arc;
 
-use crate::genome::{crossover, Genome, GenomeId};
-use crate::mutations::MutationKind;
+use crate::genome::{crossover, Genome, GenomeId, InnovationTracker};
+use crate::mutations::{apply_node_options, MutationKind};
 use crate::network::Network;
+use crate::rng::{self, NeatRng};
 use crate::speciation::SpeciesSet;
 pub use configuration::Configuration;
+pub use evaluation::{BehaviourFn, FitnessFn, ObjectivesFn, PopulationFitnessFn};
+use evaluation::{Descriptors, Evaluation};
+pub use islands::{Islands, Migration, MigrationTopology};
+pub use novelty::NoveltyArchive;
 use reporter::Reporter;
 use speciation::GenomeBank;
+pub use summary::GenerationSummary;
 
+#[cfg(feature = "checkpoint")]
+mod checkpoint;
 mod configuration;
+mod evaluation;
+mod islands;
+mod neat_python;
+mod novelty;
+mod pareto;
 mod reporter;
 mod speciation;
+mod summary;
 
 pub struct NEAT {
     inputs: usize,
     outputs: usize,
-    fitness_fn: fn(&mut Network) -> f64,
+    evaluation: Evaluation,
     pub genomes: GenomeBank,
     pub species_set: SpeciesSet,
-    configuration: Rc<RefCell<Configuration>>,
+    innovations: InnovationTracker,
+    novelty_archive: NoveltyArchive,
+    configuration: Arc<Configuration>,
     reporter: Reporter,
+    rng: NeatRng,
+    generation: usize,
+    population_seed: Option<(Vec<Genome>, f64)>,
+    /// Genomes from other islands, they join the next generation
+    immigrants: Vec<Genome>,
+    #[cfg(feature = "checkpoint")]
+    checkpoints: Option<(usize, std::path::PathBuf)>,
 }
 
 impl NEAT {
-    pub fn new(inputs: usize, outputs: usize, fitness_fn: fn(&mut Network) -> f64) -> Self {
-        let configuration: Rc<RefCell<Configuration>> = Default::default();
+    pub fn new<F>(inputs: usize, outputs: usize, fitness_fn: F) -> Self
+    where
+        F: Fn(&mut Network) -> f64 + Send + Sync + 'static,
+    {
+        NEAT::with_evaluation(
+            inputs,
+            outputs,
+            Evaluation::Individual(Box::new(fitness_fn)),
+        )
+    }
+
+    /// Creates a system where the fitness function scores the whole generation at once
+    pub fn with_population_fitness<F>(inputs: usize, outputs: usize, fitness_fn: F) -> Self
+    where
+        F: Fn(&mut [(GenomeId, Network)]) -> HashMap<GenomeId, f64> + Send + Sync + 'static,
+    {
+        NEAT::with_evaluation(
+            inputs,
+            outputs,
+            Evaluation::Population(Box::new(fitness_fn)),
+        )
+    }
+
+    /// Creates a system for novelty search, the function returns the fitness of a network and its
+    /// behaviour, selection then rewards behaviours unlike the ones seen before
+    pub fn with_novelty<F>(inputs: usize, outputs: usize, behaviour_fn: F) -> Self
+    where
+        F: Fn(&mut Network) -> (f64, Vec<f64>) + Send + Sync + 'static,
+    {
+        NEAT::with_evaluation(inputs, outputs, Evaluation::Novelty(Box::new(behaviour_fn)))
+    }
+
+    /// Creates a system that evolves networks for several objectives at once, the function returns
+    /// all of them and higher is better for each, the first one is reported as the fitness
+    pub fn with_objectives<F>(inputs: usize, outputs: usize, objectives_fn: F) -> Self
+    where
+        F: Fn(&mut Network) -> Vec<f64> + Send + Sync + 'static,
+    {
+        NEAT::with_evaluation(
+            inputs,
+            outputs,
+            Evaluation::MultiObjective(Box::new(objectives_fn)),
+        )
+    }
+
+    fn with_evaluation(inputs: usize, outputs: usize, evaluation: Evaluation) -> Self {
+        let configuration: Arc<Configuration> = Default::default();
 
         NEAT {
             inputs,
             outputs,
-            fitness_fn,
+            evaluation,
             genomes: GenomeBank::new(configuration.clone()),
             species_set: SpeciesSet::new(configuration.clone()),
+            innovations: InnovationTracker::new(inputs, outputs),
+            novelty_archive: NoveltyArchive::new(),
             configuration,
             reporter: Reporter::new(),
+            rng: rng::from_seed(None),
+            generation: 0,
+            population_seed: None,
+            immigrants: vec![],
+            #[cfg(feature = "checkpoint")]
+            checkpoints: None,
         }
     }
 
+    /// Replaces the configuration, a running system uses it from the next generation on
+    ///
+    /// Panics with the problems `Configuration::validate` finds in the configuration.
     pub fn set_configuration(&mut self, config: Configuration) {
-        *self.configuration.borrow_mut() = config;
+        if let Err(problems) = config.validate() {
+            panic!("Invalid configuration: {}", problems.join(", "));
+        }
+
+        self.configuration = Arc::new(config);
+        self.genomes.set_configuration(self.configuration.clone());
+        self.species_set
+            .set_configuration(self.configuration.clone());
     }
 
-    pub fn start(&mut self) -> (Network, f64) {
-        let (population_size, max_generations) = {
-            let config = self.configuration.borrow();
+    /// Changes some options of the current configuration between generations
+    pub fn update_configuration<F: FnOnce(&mut Configuration)>(&mut self, update: F) {
+        let mut config = Configuration::clone(&self.configuration);
+        update(&mut config);
+
+        self.set_configuration(config);
+    }
+
+    pub fn configuration(&self) -> &Configuration {
+        &self.configuration
+    }
+
+    /// Behaviours archived so far, empty unless novelty is searched
+    pub fn novelty_archive(&self) -> &NoveltyArchive {
+        &self.novelty_archive
+    }
+
+    /// Builds the initial population from the given genomes instead of fresh ones, the
+    /// remaining places are filled with copies of which `mutate_fraction` get one mutation
+    pub fn seed_population(&mut self, genomes: Vec<Genome>, mutate_fraction: f64) {
+        assert!(!genomes.is_empty(), "At least one genome is needed");
+        assert!(
+            (0. ..=1.).contains(&mutate_fraction),
+            "The mutated fraction has to be between 0 and 1, got {}",
+            mutate_fraction
+        );
 
-            (config.population_size, config.max_generations)
+        for genome in &genomes {
+            assert!(
+                genome.input_count() == self.inputs && genome.output_count() == self.outputs,
+                "Expected genomes with {} inputs and {} outputs, got {} and {}",
+                self.inputs,
+                self.outputs,
+                genome.input_count(),
+                genome.output_count()
+            );
+        }
+
+        self.population_seed = Some((genomes, mutate_fraction));
+    }
+
+    /// Creates and evaluates the initial population, discarding any previous one
+    pub fn initialize(&mut self) {
+        let (population_size, seed, initial_topology) = {
+            let config = &self.configuration;
+
+            (
+                config.population_size,
+                config.seed,
+                config.initial_topology.clone(),
+            )
         };
 
-        // Create initial genomes
-        (0..population_size).for_each(|_| {
-            self.genomes
-                .add_genome(Genome::new(self.inputs, self.outputs))
-        });
+        self.rng = rng::from_seed(seed);
+        self.generation = 0;
+        self.genomes = GenomeBank::new(self.configuration.clone());
+        self.species_set = SpeciesSet::new(self.configuration.clone());
+        self.innovations = InnovationTracker::new(self.inputs, self.outputs);
+        self.novelty_archive = NoveltyArchive::new();
+        self.immigrants.clear();
+
+        if let Some((seeds, mutate_fraction)) = self.population_seed.clone() {
+            self.add_seeded_genomes(&seeds, mutate_fraction);
+        } else {
+            (0..population_size).for_each(|_| {
+                let mut genome = Genome::with_topology(
+                    self.inputs,
+                    self.outputs,
+                    &initial_topology,
+                    &mut self.rng,
+                );
+                apply_node_options(&mut genome, &self.configuration, &mut self.rng);
+                self.genomes.add_genome(genome)
+            });
+        }
 
         self.test_fitness();
+    }
 
-        for i in 1..=max_generations {
-            let current_genome_ids: Vec<GenomeId> =
-                self.genomes.genomes().keys().cloned().collect();
-            let previous_and_current_genomes = self
-                .genomes
-                .genomes()
-                .iter()
-                .chain(self.genomes.previous_genomes())
-                .map(|(genome_id, genome)| (genome_id.clone(), genome.clone()))
-                .collect();
-
-            self.species_set.speciate(
-                i,
-                &current_genome_ids,
-                &previous_and_current_genomes,
-                self.genomes.fitnesses(),
-            );
+    fn add_seeded_genomes(&mut self, seeds: &[Genome], mutate_fraction: f64) {
+        let population_size = self.configuration.population_size;
+        let originals_count = seeds.len().min(population_size);
+        let copies_count = population_size - originals_count;
+        let mutated_count = (copies_count as f64 * mutate_fraction).round() as usize;
 
-            let (elitism, population_size, mutation_rate, survival_ratio) = {
-                let config = self.configuration.borrow();
-
-                (
-                    config.elitism,
-                    config.population_size,
-                    config.mutation_rate,
-                    config.survival_ratio,
-                )
-            };
-
-            let offspring: Vec<Genome> = self
-                .species_set
-                .species()
-                .values()
-                .flat_map(|species| {
-                    let offspring_count: usize = (species.adjusted_fitness.unwrap()
-                        * population_size as f64)
-                        .ceil() as usize;
-                    let elites_count: usize = (offspring_count as f64 * elitism).ceil() as usize;
-                    let nonelites_count: usize = offspring_count - elites_count;
-
-                    let mut member_ids_and_fitnesses: Vec<(GenomeId, f64)> = species
-                        .members
-                        .iter()
-                        .map(|member_id| {
-                            (
-                                *member_id,
-                                *self.genomes.fitnesses().get(member_id).unwrap(),
-                            )
-                        })
-                        .collect();
+        seeds.iter().for_each(|seed| self.innovations.observe(seed));
 
-                    member_ids_and_fitnesses.sort_by(|a, b| {
-                        use std::cmp::Ordering::*;
+        // Every genome gets a new id, seeds may share one when they were loaded twice
+        for seed in &seeds[..originals_count] {
+            let genome = seed.copy_with_new_id(&mut self.rng);
+            self.genomes.add_genome(genome);
+        }
 
-                        let fitness_a = a.1;
-                        let fitness_b = b.1;
+        for copy_index in 0..copies_count {
+            let mut genome = seeds[copy_index % seeds.len()].copy_with_new_id(&mut self.rng);
+
+            if copy_index < mutated_count {
+                let mutation = pick_mutation(&self.configuration.mutation_kinds, &mut self.rng);
+                genome.mutate(
+                    &mutation,
+                    &self.configuration,
+                    &mut self.innovations,
+                    &mut self.rng,
+                );
+            }
 
-                        if fitness_a > fitness_b {
-                            Less
-                        } else {
-                            Greater
-                        }
-                    });
+            self.genomes.add_genome(genome);
+        }
+    }
 
-                    // Pick survivors
-                    let surviving_count: usize =
-                        (member_ids_and_fitnesses.len() as f64 * survival_ratio).ceil() as usize;
-                    member_ids_and_fitnesses.truncate(surviving_count);
-
-                    let elite_children: Vec<Genome> =
-                        (0..usize::min(elites_count, member_ids_and_fitnesses.len()))
-                            .map(|elite_index| {
-                                let (elite_genome_id, _) =
-                                    member_ids_and_fitnesses.get(elite_index).unwrap();
-                                let elite_genome =
-                                    self.genomes.genomes().get(elite_genome_id).unwrap();
-
-                                elite_genome.clone()
-                            })
-                            .collect();
-
-                    let crossover_data: Vec<(&Genome, f64, &Genome, f64)> = (0..nonelites_count)
-                        .map(|_| {
-                            let parent_a_index = random::<usize>() % member_ids_and_fitnesses.len();
-                            let parent_b_index = random::<usize>() % member_ids_and_fitnesses.len();
-
-                            let (parent_a_id, parent_a_fitness) =
-                                member_ids_and_fitnesses.get(parent_a_index).unwrap();
-                            let (parent_b_id, parent_b_fitness) =
-                                member_ids_and_fitnesses.get(parent_b_index).unwrap();
-
-                            let parent_a_genome = self.genomes.genomes().get(parent_a_id).unwrap();
-                            let parent_b_genome = self.genomes.genomes().get(parent_b_id).unwrap();
-
-                            (
-                                parent_a_genome,
-                                *parent_a_fitness,
-                                parent_b_genome,
-                                *parent_b_fitness,
-                            )
-                        })
-                        .collect();
+    /// Speciates, reproduces and evaluates the population once
+    pub fn step_generation(&mut self) -> GenerationSummary {
+        if self.genomes.genomes().is_empty() {
+            self.initialize();
+        }
 
-                    let mut crossover_children: Vec<Genome> = crossover_data
-                        .par_iter()
-                        .map(|(parent_a, fitness_a, parent_b, fitness_b)| {
-                            crossover((parent_a, *fitness_a), (parent_b, *fitness_b))
-                        })
-                        .filter(|maybe_genome| maybe_genome.is_some())
-                        .map(|maybe_genome| maybe_genome.unwrap())
-                        .collect();
+        let generation = self.generation + 1;
 
-                    let mutations_for_children: Vec<Option<MutationKind>> = crossover_children
-                        .iter()
-                        .map(|_| {
-                            if random::<f64>() < mutation_rate {
-                                Some(self.pick_mutation())
-                            } else {
-                                None
-                            }
-                        })
-                        .collect();
+        self.speciate(generation);
 
-                    crossover_children
-                        .par_iter_mut()
-                        .zip(mutations_for_children)
-                        .for_each(|(child, maybe_mutation)| {
-                            if let Some(mutation) = maybe_mutation {
-                                child.mutate(&mutation);
-                            }
-                        });
+        let mut offspring = self.reproduce();
+        self.settle_immigrants(&mut offspring);
 
-                    elite_children
-                        .into_iter()
-                        .chain(crossover_children)
-                        .collect::<Vec<Genome>>()
-                })
-                .collect();
+        self.genomes.clear();
+        offspring
+            .into_iter()
+            .for_each(|genome| self.genomes.add_genome(genome));
 
-            self.genomes.clear();
-            offspring
-                .into_iter()
-                .for_each(|genome| self.genomes.add_genome(genome));
+        self.test_fitness();
+        self.generation = generation;
 
-            self.test_fitness();
+        self.reporter.report(generation, &self);
 
-            self.reporter.report(i, &self);
+        #[cfg(feature = "checkpoint")]
+        let checkpoint_error = self.checkpoint_if_due().err().map(|e| e.to_string());
 
-            let goal_reached = {
-                if let Some(goal) = self.configuration.borrow().fitness_goal {
-                    let (_, _, best_fitness) = self.get_best();
+        GenerationSummary {
+            #[cfg(feature = "checkpoint")]
+            checkpoint_error,
+            ..self.summary()
+        }
+    }
 
-                    best_fitness >= goal
-                } else {
-                    false
-                }
-            };
+    /// Runs generations until the fitness goal or the generation limit is reached
+    pub fn start(&mut self) -> (Network, f64) {
+        self.run();
+
+        self.best().unwrap()
+    }
 
-            if goal_reached {
+    /// Like `start`, but returns the networks no other network beats in every objective
+    pub fn start_pareto(&mut self) -> Vec<(Network, Vec<f64>)> {
+        self.run();
+
+        self.pareto_front()
+    }
+
+    fn run(&mut self) {
+        // A system resumed from a checkpoint already has its population
+        if self.genomes.genomes().is_empty() {
+            self.initialize();
+        }
+
+        while self.generation < self.configuration.max_generations {
+            if self.step_generation().goal_reached {
                 break;
             }
         }
+    }
+
+    /// Number of the last completed generation, 0 right after initialization
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// The fittest network and its fitness, `None` before initialization
+    pub fn best(&self) -> Option<(Network, f64)> {
+        if self.genomes.genomes().is_empty() {
+            return None;
+        }
 
         let (_, best_genome, best_fitness) = self.get_best();
-        (Network::from(best_genome), best_fitness)
+
+        Some((Network::from(best_genome), best_fitness))
+    }
+
+    /// Networks of the current generation no other network beats in every objective, with their
+    /// objectives, empty unless the system was created with `with_objectives`
+    pub fn pareto_front(&self) -> Vec<(Network, Vec<f64>)> {
+        let ids_and_objectives: Vec<(&GenomeId, &Vec<f64>)> =
+            self.genomes.objectives().iter().collect();
+        let objectives: Vec<&[f64]> = ids_and_objectives.iter().map(|(_, o)| &o[..]).collect();
+
+        pareto::fronts(&objectives)
+            .into_iter()
+            .next()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|index| {
+                let (genome_id, objectives) = ids_and_objectives[index];
+                let genome = self.genomes.genomes().get(genome_id).unwrap();
+
+                (Network::from(genome), objectives.clone())
+            })
+            .collect()
+    }
+
+    fn speciate(&mut self, generation: usize) {
+        let current_genome_ids: Vec<GenomeId> = self.genomes.genomes().keys().cloned().collect();
+        let previous_and_current_genomes = self
+            .genomes
+            .genomes()
+            .iter()
+            .chain(self.genomes.previous_genomes())
+            .map(|(genome_id, genome)| (*genome_id, genome.clone()))
+            .collect();
+
+        self.species_set.speciate(
+            generation,
+            &current_genome_ids,
+            &previous_and_current_genomes,
+            self.genomes.scores(),
+        );
+    }
+
+    fn reproduce(&mut self) -> Vec<Genome> {
+        let configuration = self.configuration.clone();
+        let config: &Configuration = &configuration;
+        let (elitism, elites_per_species, population_size, mutation_rate, survival_ratio) = (
+            config.elitism,
+            config.elites_per_species,
+            config.population_size,
+            config.mutation_rate,
+            config.survival_ratio,
+        );
+
+        let genomes = &self.genomes;
+        let rng = &mut self.rng;
+        let innovations = &mut self.innovations;
+        innovations.new_generation();
+
+        self.species_set
+            .species()
+            .values()
+            .flat_map(|species| {
+                // Every species gets at least room for its own elites
+                let offspring_count: usize = usize::max(
+                    (species.adjusted_fitness.unwrap() * population_size as f64).ceil() as usize,
+                    elites_per_species,
+                );
+                let elites_count: usize = usize::max(
+                    (offspring_count as f64 * elitism).ceil() as usize,
+                    elites_per_species,
+                );
+                let nonelites_count: usize = offspring_count - elites_count;
+
+                let mut member_ids_and_fitnesses: Vec<(GenomeId, f64)> = species
+                    .members
+                    .iter()
+                    .map(|member_id| (*member_id, *genomes.scores().get(member_id).unwrap()))
+                    .collect();
+
+                if genomes.objectives().is_empty() {
+                    // Fittest first, genomes without a comparable fitness last
+                    member_ids_and_fitnesses.sort_by(|a, b| {
+                        b.1.partial_cmp(&a.1)
+                            .unwrap_or_else(|| a.1.is_nan().cmp(&b.1.is_nan()))
+                    });
+                } else {
+                    // Ranked by fronts and crowding distance within the species, as in NSGA-II
+                    let member_objectives: Vec<&[f64]> = member_ids_and_fitnesses
+                        .iter()
+                        .map(|(member_id, _)| &genomes.objectives().get(member_id).unwrap()[..])
+                        .collect();
+
+                    member_ids_and_fitnesses = pareto::order(&member_objectives)
+                        .into_iter()
+                        .map(|index| member_ids_and_fitnesses[index])
+                        .collect();
+                }
+
+                // Pick survivors
+                let surviving_count: usize =
+                    (member_ids_and_fitnesses.len() as f64 * survival_ratio).ceil() as usize;
+                member_ids_and_fitnesses.truncate(surviving_count);
+
+                let elite_children: Vec<Genome> =
+                    (0..usize::min(elites_count, member_ids_and_fitnesses.len()))
+                        .map(|elite_index| {
+                            let (elite_genome_id, _) =
+                                member_ids_and_fitnesses.get(elite_index).unwrap();
+                            let elite_genome = genomes.genomes().get(elite_genome_id).unwrap();
+
+                            elite_genome.clone()
+                        })
+                        .collect();
+
+                // Every child gets its own generator so rayon can't change the outcome
+                let crossover_data: Vec<(&Genome, f64, &Genome, f64, NeatRng)> = (0
+                    ..nonelites_count)
+                    .map(|_| {
+                        let parent_a_index = rng.gen::<usize>() % member_ids_and_fitnesses.len();
+                        let parent_b_index = rng.gen::<usize>() % member_ids_and_fitnesses.len();
+
+                        let (parent_a_id, parent_a_fitness) =
+                            member_ids_and_fitnesses.get(parent_a_index).unwrap();
+                        let (parent_b_id, parent_b_fitness) =
+                            member_ids_and_fitnesses.get(parent_b_index).unwrap();
+
+                        let parent_a_genome = genomes.genomes().get(parent_a_id).unwrap();
+                        let parent_b_genome = genomes.genomes().get(parent_b_id).unwrap();
+
+                        (
+                            parent_a_genome,
+                            *parent_a_fitness,
+                            parent_b_genome,
+                            *parent_b_fitness,
+                            rng::split(rng),
+                        )
+                    })
+                    .collect();
+
+                let mut crossover_children: Vec<(Genome, NeatRng)> = crossover_data
+                    .into_par_iter()
+                    .filter_map(
+                        |(parent_a, fitness_a, parent_b, fitness_b, mut child_rng)| {
+                            crossover(
+                                (parent_a, fitness_a),
+                                (parent_b, fitness_b),
+                                config,
+                                &mut child_rng,
+                            )
+                            .map(|child| (child, child_rng))
+                        },
+                    )
+                    .collect();
+
+                let mutations_for_children: Vec<Option<MutationKind>> = crossover_children
+                    .iter()
+                    .map(|_| {
+                        if rng.gen::<f64>() < mutation_rate {
+                            Some(pick_mutation(&config.mutation_kinds, rng))
+                        } else {
+                            None
+                        }
+                    })
+                    .collect();
+
+                // Mutations share the innovation tracker, so they are applied in order
+                crossover_children
+                    .iter_mut()
+                    .zip(mutations_for_children)
+                    .for_each(|((child, child_rng), maybe_mutation)| {
+                        if let Some(mutation) = maybe_mutation {
+                            child.mutate(&mutation, config, innovations, child_rng);
+                        }
+                    });
+
+                elite_children
+                    .into_iter()
+                    .chain(crossover_children.into_iter().map(|(child, _)| child))
+                    .collect::<Vec<Genome>>()
+            })
+            .collect()
+    }
+
+    fn summary(&self) -> GenerationSummary {
+        let fitnesses = self.genomes.fitnesses();
+        let (_, _, best_fitness) = self.get_best();
+        let mean_fitness = fitnesses.values().sum::<f64>() / fitnesses.len() as f64;
+
+        let goal_reached = match self.configuration.fitness_goal {
+            Some(goal) => best_fitness >= goal,
+            None => false,
+        };
+
+        GenerationSummary {
+            generation: self.generation,
+            best_fitness,
+            mean_fitness,
+            population_size: fitnesses.len(),
+            species_count: self.species_set.species().len(),
+            goal_reached,
+            #[cfg(feature = "checkpoint")]
+            checkpoint_error: None,
+        }
     }
 
     fn test_fitness(&mut self) {
-        let ids_and_networks: Vec<(GenomeId, Network)> = self
+        let mut ids_and_networks: Vec<(GenomeId, Network)> = self
             .genomes
             .genomes()
             .iter()
             .map(|(genome_id, genome)| (*genome_id, Network::from(genome)))
             .collect();
 
-        let node_cost = self.configuration.borrow().node_cost;
-        let connection_cost = self.configuration.borrow().connection_cost;
-        let fitness_fn = self.fitness_fn;
+        let node_cost = self.configuration.node_cost;
+        let connection_cost = self.configuration.connection_cost;
+        let non_finite_fitness = self.configuration.non_finite_fitness;
+
+        let (fitnesses, descriptors) = self.evaluation.evaluate(&mut ids_and_networks);
 
         let ids_and_fitnesses: Vec<(GenomeId, f64)> = ids_and_networks
-            .into_par_iter()
-            .map(|(genome_id, mut network)| {
-                let mut fitness: f64 = (fitness_fn)(&mut network);
+            .iter()
+            .zip(fitnesses)
+            .map(|((genome_id, network), mut fitness)| {
                 fitness -= node_cost * network.nodes.len() as f64;
                 fitness -= connection_cost * network.connections.len() as f64;
 
-                (genome_id, fitness)
+                // A single NaN would poison the mean fitness of the whole species
+                if !fitness.is_finite() {
+                    fitness = non_finite_fitness;
+                }
+
+                (*genome_id, fitness)
             })
             .collect();
 
-        ids_and_fitnesses
-            .into_iter()
-            .for_each(|(genome_id, genome_fitness)| {
-                self.genomes.mark_fitness(genome_id, genome_fitness)
-            });
-    }
+        let scores: Vec<f64> = match descriptors {
+            Descriptors::Behaviours(behaviours) => {
+                let novelty_weight = self.configuration.novelty_weight;
+                let novelties = self.novelty_archive.score(
+                    &behaviours,
+                    self.configuration.novelty_neighbours,
+                    self.configuration.novelty_threshold,
+                );
+
+                ids_and_fitnesses
+                    .iter()
+                    .zip(novelties)
+                    .map(|((_, fitness), novelty)| {
+                        let score = novelty_weight * novelty + (1. - novelty_weight) * fitness;
+
+                        if score.is_finite() {
+                            score
+                        } else {
+                            non_finite_fitness
+                        }
+                    })
+                    .collect()
+            }
+            Descriptors::Objectives(mut objectives) => {
+                objectives.iter_mut().flatten().for_each(|objective| {
+                    if !objective.is_finite() {
+                        *objective = non_finite_fitness;
+                    }
+                });
+
+                // Species and survivors are picked by the front genomes belong to
+                let ranks = {
+                    let objectives: Vec<&[f64]> = objectives.iter().map(|o| &o[..]).collect();
+                    pareto::ranks(&objectives)
+                };
+
+                ids_and_fitnesses.iter().zip(objectives).for_each(
+                    |((genome_id, _), objectives)| {
+                        self.genomes.mark_objectives(*genome_id, objectives)
+                    },
+                );
+
+                ranks.into_iter().map(|rank| -(rank as f64)).collect()
+            }
+            Descriptors::None => ids_and_fitnesses
+                .iter()
+                .map(|(_, fitness)| *fitness)
+                .collect(),
+        };
 
-    pub fn get_best(&self) -> (GenomeId, &Genome, f64) {
-        let (best_genome_id, best_fitness) = self.genomes.fitnesses().iter().fold(
-            (Uuid::new_v4(), f64::MIN),
-            |(best_id, best_fitness), (genome_id, genome_fitness)| {
-                if *genome_fitness > best_fitness {
-                    (*genome_id, *genome_fitness)
-                } else {
-                    (best_id, best_fitness)
-                }
+        ids_and_fitnesses.into_iter().zip(scores).for_each(
+            |((genome_id, genome_fitness), score)| {
+                self.genomes.mark_fitness(genome_id, genome_fitness);
+                self.genomes.mark_score(genome_id, score);
             },
         );
-
-        let best_genome = self.genomes.genomes().get(&best_genome_id).unwrap();
-
-        (best_genome_id, best_genome, best_fitness)
     }
 
-    fn pick_mutation(&self) -> MutationKind {
-        use rand::{distributions::Distribution, thread_rng};
-        use rand_distr::weighted_alias::WeightedAliasIndex;
-
-        let dist = WeightedAliasIndex::new(
-            self.configuration
-                .borrow()
-                .mutation_kinds
-                .iter()
-                .map(|k| k.1)
-                .collect(),
-        )
-        .unwrap();
+    pub fn get_best(&self) -> (GenomeId, &Genome, f64) {
+        let (best_genome_id, best_fitness) = self
+            .genomes
+            .fitnesses()
+            .iter()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .unwrap();
 
-        let mut rng = thread_rng();
+        let best_genome = self.genomes.genomes().get(best_genome_id).unwrap();
 
-        self.configuration
-            .borrow()
-            .mutation_kinds
-            .get(dist.sample(&mut rng))
-            .cloned()
-            .unwrap()
-            .0
+        (*best_genome_id, best_genome, *best_fitness)
     }
 
     pub fn add_hook(&mut self, every: usize, hook: reporter::Hook) {
@@ -304,21 +624,40 @@ impl NEAT {
     }
 }
 
+pub(crate) fn pick_mutation<R: Rng + ?Sized>(
+    mutation_kinds: &[(MutationKind, usize)],
+    rng: &mut R,
+) -> MutationKind {
+    use rand::distributions::Distribution;
+    use rand_distr::weighted_alias::WeightedAliasIndex;
+
+    let dist = WeightedAliasIndex::new(mutation_kinds.iter().map(|k| k.1).collect()).unwrap();
+
+    mutation_kinds.get(dist.sample(rng)).cloned().unwrap().0
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// A fitness for runs that only need to be reproducible, peaks when the output is 1 while only
+    /// the first input is on
+    pub(super) fn first_input_to_one(n: &mut Network) -> f64 {
+        let output = n.forward_pass(&vec![1., 0.]);
+
+        1. / (1. + (1. - output[0]).powi(2))
+    }
+
     #[test]
     fn xor() {
-        let mut system = NEAT::new(2, 1, |n| {
-            let inputs: Vec<Vec<f64>> =
-                vec![vec![0., 0.], vec![0., 1.], vec![1., 0.], vec![1., 1.]];
-            let outputs: Vec<f64> = vec![0., 1., 1., 0.];
+        let inputs: Vec<Vec<f64>> = vec![vec![0., 0.], vec![0., 1.], vec![1., 0.], vec![1., 1.]];
+        let outputs: Vec<f64> = vec![0., 1., 1., 0.];
 
+        let mut system = NEAT::new(2, 1, move |n| {
             let mut error = 0.;
 
-            for (i, o) in inputs.iter().zip(outputs) {
-                let results = n.forward_pass(i.clone());
+            for (i, o) in inputs.iter().zip(&outputs) {
+                let results = n.forward_pass(i);
                 let result = results.first().unwrap();
 
                 error += (o - *result).powi(2);
@@ -346,7 +685,7 @@ mod tests {
 
         let inputs: Vec<Vec<f64>> = vec![vec![0., 0.], vec![0., 1.], vec![1., 0.], vec![1., 1.]];
         for i in inputs {
-            let o = network.forward_pass(i.clone());
+            let o = network.forward_pass(&i);
             dbg!(i, o);
         }
 
@@ -359,4 +698,299 @@ mod tests {
             fitness
         );
     }
+
+    #[test]
+    fn non_finite_fitness_is_replaced() {
+        let mut system = NEAT::new(2, 1, |_| f64::NAN);
+
+        system.set_configuration(Configuration {
+            population_size: 10,
+            max_generations: 3,
+            non_finite_fitness: -1.,
+            ..Default::default()
+        });
+
+        let (_, fitness) = system.start();
+        assert_eq!(fitness, -1.);
+        assert!(system.genomes.fitnesses().values().all(|f| *f == -1.));
+    }
+
+    #[test]
+    fn species_keep_their_elites() {
+        let mut system = NEAT::new(2, 1, first_input_to_one);
+
+        system.set_configuration(Configuration {
+            population_size: 30,
+            elitism: 0.,
+            elites_per_species: 2,
+            seed: Some(3),
+            ..Default::default()
+        });
+        system.initialize();
+        system.speciate(1);
+
+        let elites: Vec<GenomeId> = system
+            .species_set
+            .species()
+            .values()
+            .flat_map(|species| {
+                let mut members = species.members.clone();
+                members.sort_by(|a, b| {
+                    let score = |id| system.genomes.scores().get(id).unwrap();
+                    score(b).total_cmp(score(a))
+                });
+                members.truncate(2);
+
+                members
+            })
+            .collect();
+
+        let offspring: Vec<GenomeId> = system.reproduce().iter().map(Genome::id).collect();
+
+        assert!(elites.iter().all(|id| offspring.contains(id)));
+    }
+
+    #[test]
+    fn runs_on_another_thread() {
+        let mut system = NEAT::new(2, 1, |_| 1.);
+
+        system.set_configuration(Configuration {
+            population_size: 30,
+            max_generations: 3,
+            ..Default::default()
+        });
+
+        let system = std::thread::spawn(move || {
+            system.step_generation();
+            system
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(system.generation(), 1);
+    }
+
+    #[test]
+    fn configuration_changes_between_generations() {
+        let mut system = NEAT::new(2, 1, |_| 1.);
+
+        system.set_configuration(Configuration {
+            population_size: 30,
+            ..Default::default()
+        });
+        system.step_generation();
+
+        system.update_configuration(|config| config.population_size = 20);
+        let summary = system.step_generation();
+
+        assert_eq!(system.configuration().population_size, 20);
+        assert!(summary.population_size < 30);
+    }
+
+    #[test]
+    #[should_panic(expected = "mutation_kinds needs at least one kind with a weight above 0")]
+    fn invalid_configuration_changes_are_rejected() {
+        let mut system = NEAT::new(2, 1, |_| 1.);
+
+        system.update_configuration(|config| {
+            config
+                .mutation_kinds
+                .iter_mut()
+                .for_each(|(_, weight)| *weight = 0)
+        });
+    }
+
+    #[test]
+    fn seeded_runs_are_identical() {
+        let run = || {
+            let mut system = NEAT::new(2, 1, first_input_to_one);
+
+            system.set_configuration(Configuration {
+                population_size: 30,
+                max_generations: 10,
+                seed: Some(1337),
+                ..Default::default()
+            });
+            system.start();
+
+            system
+                .genomes
+                .genomes()
+                .iter()
+                .map(|(genome_id, genome)| {
+                    let fitness = system.genomes.fitnesses().get(genome_id).unwrap();
+
+                    (genome.clone(), fitness.to_bits())
+                })
+                .collect::<Vec<(Genome, u64)>>()
+        };
+
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn evolve_from_unconnected_genomes() {
+        // Connections have to be evolved from nothing
+        let mut system = NEAT::new(10, 1, |n| n.connections.len() as f64);
+
+        system.set_configuration(Configuration {
+            population_size: 30,
+            max_generations: 10,
+            initial_topology: crate::genome::InitialTopology::None,
+            ..Default::default()
+        });
+
+        // Unconnected children must survive crossover, or the population shrinks
+        system.initialize();
+        for _ in 0..10 {
+            assert!(system.step_generation().population_size >= 30);
+        }
+
+        let (network, _) = system.best().unwrap();
+        assert!(network.nodes.len() >= 11);
+        assert!(!network.connections.is_empty());
+    }
+
+    #[test]
+    fn stepping_matches_start() {
+        let system = || {
+            let mut system = NEAT::new(2, 1, first_input_to_one);
+
+            system.set_configuration(Configuration {
+                population_size: 30,
+                max_generations: 5,
+                seed: Some(7),
+                ..Default::default()
+            });
+
+            system
+        };
+
+        let mut started = system();
+        started.start();
+
+        let mut stepped = system();
+        assert!(stepped.best().is_none());
+
+        stepped.initialize();
+        assert_eq!(stepped.generation(), 0);
+
+        let summaries: Vec<GenerationSummary> = (0..5).map(|_| stepped.step_generation()).collect();
+
+        assert_eq!(summaries.last().unwrap().generation, 5);
+        assert_eq!(stepped.generation(), started.generation());
+        assert_eq!(stepped.best().unwrap().1, started.best().unwrap().1);
+        assert_eq!(
+            stepped.genomes.genomes().values().collect::<Vec<_>>(),
+            started.genomes.genomes().values().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn seeded_population() {
+        let mut rng = rand::thread_rng();
+        let mut seed = Genome::new_with_rng(2, 1, &mut rng);
+        let hidden = seed.add_node(&mut rng);
+        seed.add_connection(0, hidden, &mut rng).unwrap();
+
+        let mut system = NEAT::new(2, 1, |_| 1.);
+
+        system.set_configuration(Configuration {
+            population_size: 20,
+            seed: Some(3),
+            ..Default::default()
+        });
+        system.seed_population(vec![seed.clone(), seed.clone()], 0.5);
+        system.initialize();
+
+        let genomes = system.genomes.genomes();
+        assert_eq!(genomes.len(), 20);
+
+        // Mutations only ever add genes
+        assert!(genomes
+            .values()
+            .all(|g| g.nodes().len() >= seed.nodes().len()
+                && g.connections().len() >= seed.connections().len()));
+        assert!(genomes.values().all(|g| g.id() != seed.id()));
+
+        let untouched = genomes
+            .values()
+            .filter(|g| g.nodes() == seed.nodes() && g.connections() == seed.connections())
+            .count();
+        assert!(untouched >= 2 + 9);
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected genomes with 2 inputs and 1 outputs")]
+    fn seeded_population_checks_sizes() {
+        let mut system = NEAT::new(2, 1, |_| 0.);
+
+        system.seed_population(vec![Genome::new(3, 1)], 0.);
+    }
+
+    #[test]
+    fn novelty_search() {
+        let novelty_system = |novelty_weight: f64| {
+            let mut system = NEAT::with_novelty(2, 1, |n| {
+                let output = n.forward_pass(&vec![1., 0.]);
+
+                (output[0], output)
+            });
+
+            system.set_configuration(Configuration {
+                population_size: 30,
+                max_generations: 5,
+                novelty_neighbours: 5,
+                novelty_threshold: 0.05,
+                novelty_weight,
+                seed: Some(11),
+                ..Default::default()
+            });
+            system.start();
+
+            system
+        };
+
+        let system = novelty_system(1.);
+        assert!(!system.novelty_archive().behaviours().is_empty());
+        assert!(system
+            .genomes
+            .scores()
+            .values()
+            .zip(system.genomes.fitnesses().values())
+            .any(|(score, fitness)| score != fitness));
+
+        // Without any weight on novelty selection only sees fitness
+        let system = novelty_system(0.);
+        assert_eq!(system.genomes.scores(), system.genomes.fitnesses());
+    }
+
+    #[test]
+    fn multi_objective_returns_pareto_front() {
+        let mut system = NEAT::with_objectives(2, 1, |n| {
+            let output = n.forward_pass(&vec![1., 0.]);
+
+            vec![output[0], -(n.connections.len() as f64)]
+        });
+
+        system.set_configuration(Configuration {
+            population_size: 30,
+            max_generations: 5,
+            seed: Some(5),
+            ..Default::default()
+        });
+
+        let front = system.start_pareto();
+        assert!(!front.is_empty());
+
+        for (_, a) in &front {
+            assert_eq!(a.len(), 2);
+            assert!(front
+                .iter()
+                .all(|(_, b)| !(b[0] >= a[0] && b[1] >= a[1] && (b[0] > a[0] || b[1] > a[1]))));
+        }
+
+        let (_, best_fitness) = system.best().unwrap();
+        assert!(front.iter().any(|(_, o)| o[0] == best_fitness));
+    }
 }
@@ -0,0 +1,419 @@
+use std::collections::BTreeMap;
+use std::fs::read_to_string;
+use std::io;
+use std::path::Path;
+
+use super::configuration::Configuration;
+use crate::activation::ActivationKind;
+use crate::aggregations::Aggregation;
+use crate::genome::InitialTopology;
+use crate::mutations::MutationKind;
+
+type Sections = BTreeMap<String, BTreeMap<String, String>>;
+
+/// Reads a neat-python `.ini` configuration
+impl Configuration {
+    /// Returns the configuration together with notes about every option that has no equivalent
+    /// or was only approximated. Mutation probabilities become sampling weights, because only one
+    /// mutation is applied to each offspring
+    pub fn from_neat_python(ini: &str) -> io::Result<(Self, Vec<String>)> {
+        let mut sections = parse_ini(ini)?;
+        let mut configuration = Configuration::default();
+        let mut notes = vec![];
+
+        if let Some(mut neat) = sections.remove("NEAT") {
+            if let Some(criterion) = neat.remove("fitness_criterion") {
+                if criterion != "max" {
+                    notes.push(format!(
+                        "[NEAT] fitness_criterion = {} has no equivalent, fitness is always maximized",
+                        criterion
+                    ));
+                }
+            }
+            if let Some(threshold) = neat.remove("fitness_threshold") {
+                configuration.fitness_goal = Some(parse(&threshold)?);
+            }
+            if let Some(no_termination) = neat.remove("no_fitness_termination") {
+                if parse_bool(&no_termination)? {
+                    configuration.fitness_goal = None;
+                }
+            }
+            if let Some(pop_size) = neat.remove("pop_size") {
+                configuration.population_size = parse(&pop_size)?;
+            }
+
+            unsupported("NEAT", neat, &mut notes);
+        }
+
+        if let Some(mut genome) = sections.remove("DefaultGenome") {
+            for key in &["num_inputs", "num_outputs"] {
+                if let Some(value) = genome.remove(*key) {
+                    notes.push(format!(
+                        "[DefaultGenome] {} = {} has to be passed to NEAT::new",
+                        key, value
+                    ));
+                }
+            }
+            if let Some(hidden) = genome.remove("num_hidden") {
+                if parse::<usize>(&hidden)? > 0 {
+                    notes.push(format!(
+                        "[DefaultGenome] num_hidden = {} has no equivalent, genomes start without hidden nodes",
+                        hidden
+                    ));
+                }
+            }
+            if let Some(feed_forward) = genome.remove("feed_forward") {
+                configuration.recurrent_connections = !parse_bool(&feed_forward)?;
+            }
+            if let Some(initial_connection) = genome.remove("initial_connection") {
+                configuration.initial_topology = initial_topology(&initial_connection, &mut notes)?;
+            }
+
+            if let Some(options) = genome.remove("activation_options") {
+                configuration.activation_options = options
+                    .split_whitespace()
+                    .filter_map(|name| {
+                        let activation = activation(name);
+                        if activation.is_none() {
+                            notes.push(format!(
+                                "[DefaultGenome] activation {} has no equivalent",
+                                name
+                            ));
+                        }
+                        activation
+                    })
+                    .collect();
+            }
+            if let Some(options) = genome.remove("aggregation_options") {
+                configuration.aggregation_options = options
+                    .split_whitespace()
+                    .filter_map(|name| {
+                        let aggregation = aggregation(name);
+                        if aggregation.is_none() {
+                            notes.push(format!(
+                                "[DefaultGenome] aggregation {} has no equivalent",
+                                name
+                            ));
+                        }
+                        aggregation
+                    })
+                    .collect();
+            }
+
+            if let Some(coefficient) = genome.remove("compatibility_disjoint_coefficient") {
+                configuration.distance_connection_disjoint_coefficient = parse(&coefficient)?;
+            }
+            if let Some(coefficient) = genome.remove("compatibility_weight_coefficient") {
+                // neat-python uses it for the bias difference of nodes too
+                let coefficient = parse(&coefficient)?;
+                configuration.distance_connection_weight_coeficcient = coefficient;
+                configuration.distance_node_bias_coefficient = coefficient;
+            }
+
+            let mut mutation_kinds = vec![];
+            for (key, kind) in &[
+                ("conn_add_prob", MutationKind::AddConnection),
+                ("conn_delete_prob", MutationKind::RemoveConnection),
+                ("node_add_prob", MutationKind::AddNode),
+                ("node_delete_prob", MutationKind::RemoveNode),
+                ("weight_mutate_rate", MutationKind::ModifyWeight),
+                ("bias_mutate_rate", MutationKind::ModifyBias),
+                ("activation_mutate_rate", MutationKind::ModifyActivation),
+                ("aggregation_mutate_rate", MutationKind::ModifyAggregation),
+            ] {
+                if let Some(probability) = genome.remove(*key) {
+                    let weight = (parse::<f64>(&probability)? * 100.).round() as usize;
+                    mutation_kinds.push((kind.clone(), weight));
+                }
+            }
+            if !mutation_kinds.is_empty() {
+                configuration.mutation_kinds = mutation_kinds;
+            }
+
+            unsupported("DefaultGenome", genome, &mut notes);
+        }
+
+        if let Some(mut species_set) = sections.remove("DefaultSpeciesSet") {
+            if let Some(threshold) = species_set.remove("compatibility_threshold") {
+                configuration.compatibility_threshold = parse(&threshold)?;
+            }
+
+            unsupported("DefaultSpeciesSet", species_set, &mut notes);
+        }
+
+        if let Some(mut stagnation) = sections.remove("DefaultStagnation") {
+            if let Some(fitness_func) = stagnation.remove("species_fitness_func") {
+                if fitness_func != "mean" {
+                    notes.push(format!(
+                        "[DefaultStagnation] species_fitness_func = {} has no equivalent, species use the mean",
+                        fitness_func
+                    ));
+                }
+            }
+            if let Some(max_stagnation) = stagnation.remove("max_stagnation") {
+                configuration.stagnation_after = parse(&max_stagnation)?;
+            }
+            if let Some(species_elitism) = stagnation.remove("species_elitism") {
+                configuration.elitism_species = parse(&species_elitism)?;
+            }
+
+            unsupported("DefaultStagnation", stagnation, &mut notes);
+        }
+
+        if let Some(mut reproduction) = sections.remove("DefaultReproduction") {
+            if let Some(elitism) = reproduction.remove("elitism") {
+                // neat-python keeps a number of elites per species instead of a ratio
+                configuration.elites_per_species = parse(&elitism)?;
+                configuration.elitism = 0.;
+            }
+            if let Some(threshold) = reproduction.remove("survival_threshold") {
+                configuration.survival_ratio = parse(&threshold)?;
+            }
+
+            unsupported("DefaultReproduction", reproduction, &mut notes);
+        }
+
+        for (name, options) in sections {
+            unsupported(&name, options, &mut notes);
+        }
+
+        configuration
+            .validate()
+            .map_err(|problems| invalid_data(problems.join(", ")))?;
+
+        Ok((configuration, notes))
+    }
+
+    pub fn from_neat_python_file<P: AsRef<Path>>(path: P) -> io::Result<(Self, Vec<String>)> {
+        Configuration::from_neat_python(&read_to_string(path)?)
+    }
+}
+
+fn invalid_data<S: Into<String>>(message: S) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Sections with their keys and values, indented lines continue the previous value
+fn parse_ini(ini: &str) -> io::Result<Sections> {
+    let mut sections = Sections::new();
+    let mut section: Option<String> = None;
+    let mut key: Option<String> = None;
+
+    for line in ini.lines() {
+        let content = line.trim();
+        if content.is_empty() || content.starts_with('#') || content.starts_with(';') {
+            continue;
+        }
+
+        if line.starts_with(char::is_whitespace) {
+            if let (Some(section), Some(key)) = (&section, &key) {
+                let value = sections.get_mut(section).unwrap().get_mut(key).unwrap();
+                value.push(' ');
+                value.push_str(content);
+                continue;
+            }
+        }
+
+        if content.starts_with('[') && content.ends_with(']') {
+            let name = content[1..content.len() - 1].trim().to_string();
+            sections.entry(name.clone()).or_default();
+            section = Some(name);
+            key = None;
+        } else if let Some(separator) = content.find(&['=', ':'][..]) {
+            let name = section
+                .as_ref()
+                .ok_or_else(|| invalid_data(format!("{} is outside of a section", content)))?;
+            let option = content[..separator].trim().to_string();
+            let value = content[separator + 1..].trim().to_string();
+
+            sections
+                .get_mut(name)
+                .unwrap()
+                .insert(option.clone(), value);
+            key = Some(option);
+        } else {
+            return Err(invalid_data(format!("Can't parse the line {}", content)));
+        }
+    }
+
+    Ok(sections)
+}
+
+fn parse<T: std::str::FromStr>(value: &str) -> io::Result<T> {
+    value
+        .parse()
+        .map_err(|_| invalid_data(format!("{} is not a valid value", value)))
+}
+
+fn parse_bool(value: &str) -> io::Result<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => Err(invalid_data(format!("{} is not a boolean", value))),
+    }
+}
+
+fn unsupported(section: &str, options: BTreeMap<String, String>, notes: &mut Vec<String>) {
+    options.into_iter().for_each(|(key, value)| {
+        notes.push(format!(
+            "[{}] {} = {} has no equivalent",
+            section, key, value
+        ))
+    });
+}
+
+fn initial_topology(value: &str, notes: &mut Vec<String>) -> io::Result<InitialTopology> {
+    let mut parts = value.split_whitespace();
+    let kind = parts.next().unwrap_or("");
+
+    let topology = match kind {
+        "unconnected" => InitialTopology::None,
+        "fs_neat" | "fs_neat_nohidden" | "fs_neat_hidden" => InitialTopology::FsNeat,
+        "full" | "full_direct" | "full_nodirect" => InitialTopology::Full,
+        "partial" | "partial_direct" | "partial_nodirect" => {
+            let fraction = parts
+                .next()
+                .ok_or_else(|| invalid_data(format!("{} needs a fraction", kind)))?;
+            InitialTopology::Fraction(parse(fraction)?)
+        }
+        _ => {
+            notes.push(format!(
+                "[DefaultGenome] initial_connection = {} has no equivalent",
+                value
+            ));
+            InitialTopology::Full
+        }
+    };
+
+    Ok(topology)
+}
+
+fn activation(name: &str) -> Option<ActivationKind> {
+    match name {
+        "sigmoid" => Some(ActivationKind::Logistic),
+        "tanh" => Some(ActivationKind::Tanh),
+        "sin" => Some(ActivationKind::Sinusoid),
+        "gauss" => Some(ActivationKind::Gaussian),
+        "relu" => Some(ActivationKind::Relu),
+        "selu" => Some(ActivationKind::SELU),
+        "identity" => Some(ActivationKind::Identity),
+        "inv" => Some(ActivationKind::Inverse),
+        _ => None,
+    }
+}
+
+fn aggregation(name: &str) -> Option<Aggregation> {
+    match name {
+        "sum" => Some(Aggregation::Sum),
+        "product" => Some(Aggregation::Product),
+        "max" => Some(Aggregation::Max),
+        "min" => Some(Aggregation::Min),
+        "maxabs" => Some(Aggregation::MaxAbs),
+        "median" => Some(Aggregation::Median),
+        "mean" => Some(Aggregation::Mean),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The XOR example config of neat-python
+    const XOR_CONFIG: &str = r#"
+[NEAT]
+fitness_criterion     = max
+fitness_threshold     = 3.9
+pop_size              = 150
+reset_on_extinction   = False
+
+[DefaultGenome]
+# node activation options
+activation_default      = sigmoid
+activation_mutate_rate  = 0.0
+activation_options      = sigmoid
+
+# node aggregation options
+aggregation_default     = sum
+aggregation_mutate_rate = 0.0
+aggregation_options     = sum
+
+bias_init_mean          = 0.0
+bias_mutate_rate        = 0.7
+
+compatibility_disjoint_coefficient = 1.0
+compatibility_weight_coefficient   = 0.5
+
+conn_add_prob           = 0.5
+conn_delete_prob        = 0.5
+
+feed_forward            = True
+initial_connection      = full
+
+node_add_prob           = 0.2
+node_delete_prob        = 0.2
+
+num_hidden              = 0
+num_inputs              = 2
+num_outputs             = 1
+
+weight_mutate_rate      = 0.8
+
+[DefaultSpeciesSet]
+compatibility_threshold = 3.0
+
+[DefaultStagnation]
+species_fitness_func = max
+max_stagnation       = 20
+
+[DefaultReproduction]
+elitism            = 2
+survival_threshold = 0.2
+"#;
+
+    #[test]
+    fn imports_xor_config() {
+        let (configuration, notes) = Configuration::from_neat_python(XOR_CONFIG).unwrap();
+
+        assert_eq!(configuration.population_size, 150);
+        assert_eq!(configuration.fitness_goal, Some(3.9));
+        assert_eq!(
+            configuration.activation_options,
+            vec![ActivationKind::Logistic]
+        );
+        assert_eq!(configuration.aggregation_options, vec![Aggregation::Sum]);
+        assert_eq!(configuration.stagnation_after, 20);
+        assert_eq!(configuration.survival_ratio, 0.2);
+        assert_eq!(configuration.elites_per_species, 2);
+        assert_eq!(configuration.elitism, 0.);
+        assert!(!configuration.recurrent_connections);
+        assert!(configuration
+            .mutation_kinds
+            .contains(&(MutationKind::AddConnection, 50)));
+
+        let reported = |key: &str| notes.iter().any(|note| note.contains(key));
+        assert!(reported("reset_on_extinction"));
+        assert!(reported("bias_init_mean"));
+        assert!(reported("species_fitness_func"));
+        assert!(reported("num_inputs"));
+        assert!(!reported("pop_size"));
+        assert!(!reported("elitism"));
+    }
+
+    #[test]
+    fn reports_unknown_activations_and_bad_values() {
+        let (configuration, notes) = Configuration::from_neat_python(
+            "[DefaultGenome]\nactivation_options = tanh\n    clamped relu\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            configuration.activation_options,
+            vec![ActivationKind::Tanh, ActivationKind::Relu]
+        );
+        assert_eq!(notes.len(), 1);
+
+        assert!(Configuration::from_neat_python("[NEAT]\npop_size = many\n").is_err());
+        assert!(Configuration::from_neat_python("pop_size = 10\n").is_err());
+    }
+}
@@ -0,0 +1,106 @@
+/// Behaviours that were novel when they appeared, later behaviours are compared against them
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(
+    feature = "checkpoint",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct NoveltyArchive {
+    behaviours: Vec<Vec<f64>>,
+}
+
+impl NoveltyArchive {
+    pub fn new() -> Self {
+        NoveltyArchive::default()
+    }
+
+    pub fn behaviours(&self) -> &[Vec<f64>] {
+        &self.behaviours
+    }
+
+    /// Novelty of every behaviour of a generation, the mean distance to its `neighbours` nearest
+    /// behaviours among the others and the archive
+    ///
+    /// Behaviours with a novelty above `threshold` are archived afterwards.
+    pub fn score(
+        &mut self,
+        behaviours: &[Vec<f64>],
+        neighbours: usize,
+        threshold: f64,
+    ) -> Vec<f64> {
+        let novelties: Vec<f64> = behaviours
+            .iter()
+            .enumerate()
+            .map(|(index, behaviour)| {
+                let mut distances: Vec<f64> = behaviours
+                    .iter()
+                    .enumerate()
+                    .filter(|(other_index, _)| *other_index != index)
+                    .map(|(_, other)| other)
+                    .chain(self.behaviours.iter())
+                    .map(|other| distance(behaviour, other))
+                    .collect();
+
+                sparseness(&mut distances, neighbours)
+            })
+            .collect();
+
+        behaviours
+            .iter()
+            .zip(&novelties)
+            .filter(|(_, novelty)| **novelty > threshold)
+            .for_each(|(behaviour, _)| self.behaviours.push(behaviour.clone()));
+
+        novelties
+    }
+}
+
+/// Mean of the `neighbours` smallest distances, 0 when there's nothing to compare with
+fn sparseness(distances: &mut [f64], neighbours: usize) -> f64 {
+    if distances.is_empty() {
+        return 0.;
+    }
+
+    distances.sort_by(|a, b| a.total_cmp(b));
+    let nearest = &distances[..neighbours.min(distances.len())];
+
+    nearest.iter().sum::<f64>() / nearest.len() as f64
+}
+
+/// Euclidean distance between two behaviours of the same length
+fn distance(a: &[f64], b: &[f64]) -> f64 {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (a - b).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn novelty_is_mean_distance_to_nearest_neighbours() {
+        let mut archive = NoveltyArchive::new();
+        let behaviours = vec![vec![0., 0.], vec![0., 1.], vec![0., 3.]];
+
+        let novelties = archive.score(&behaviours, 1, 10.);
+        assert_eq!(novelties, vec![1., 1., 2.]);
+
+        let novelties = archive.score(&behaviours, 2, 10.);
+        assert_eq!(novelties, vec![2., 1.5, 2.5]);
+    }
+
+    #[test]
+    fn novel_behaviours_are_archived() {
+        let mut archive = NoveltyArchive::new();
+
+        archive.score(&[vec![0.], vec![1.], vec![5.]], 1, 2.);
+        assert_eq!(archive.behaviours(), &[vec![5.]]);
+
+        // The archived behaviour makes the same one less novel
+        let novelties = archive.score(&[vec![5.], vec![0.]], 1, 2.);
+        assert_eq!(novelties, vec![0., 5.]);
+        assert_eq!(archive.behaviours(), &[vec![5.], vec![0.]]);
+    }
+}
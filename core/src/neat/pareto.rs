@@ -0,0 +1,122 @@
+/// Whether `a` is at least as good as `b` in every objective and better in one
+fn dominates(a: &[f64], b: &[f64]) -> bool {
+    a.iter().zip(b).all(|(a, b)| a >= b) && a.iter().zip(b).any(|(a, b)| a > b)
+}
+
+/// Splits the indexes into non-dominated fronts, the first front is dominated by nothing
+pub(crate) fn fronts(objectives: &[&[f64]]) -> Vec<Vec<usize>> {
+    let count = objectives.len();
+    let mut dominated_by_count = vec![0; count];
+    let mut dominated: Vec<Vec<usize>> = vec![vec![]; count];
+
+    for i in 0..count {
+        for j in 0..count {
+            if dominates(objectives[i], objectives[j]) {
+                dominated[i].push(j);
+            } else if dominates(objectives[j], objectives[i]) {
+                dominated_by_count[i] += 1;
+            }
+        }
+    }
+
+    let mut fronts = vec![];
+    let mut front: Vec<usize> = (0..count).filter(|i| dominated_by_count[*i] == 0).collect();
+
+    while !front.is_empty() {
+        let mut next_front = vec![];
+
+        for i in &front {
+            for j in &dominated[*i] {
+                dominated_by_count[*j] -= 1;
+                if dominated_by_count[*j] == 0 {
+                    next_front.push(*j);
+                }
+            }
+        }
+
+        next_front.sort_unstable();
+        fronts.push(front);
+        front = next_front;
+    }
+
+    fronts
+}
+
+/// How far apart the neighbours of every member of a front are, the extremes are infinitely far
+fn crowding_distances(objectives: &[&[f64]], front: &[usize]) -> Vec<f64> {
+    let mut distances = vec![0.; front.len()];
+    let objective_count = front.first().map_or(0, |i| objectives[*i].len());
+
+    // Values of every objective for the members of the front
+    let columns: Vec<Vec<f64>> = (0..objective_count)
+        .map(|objective| front.iter().map(|i| objectives[*i][objective]).collect())
+        .collect();
+
+    for values in &columns {
+        let mut order: Vec<usize> = (0..front.len()).collect();
+        order.sort_by(|a, b| values[*a].total_cmp(&values[*b]));
+
+        let (first, last) = (order[0], order[order.len() - 1]);
+        let range = values[last] - values[first];
+
+        distances[first] = f64::INFINITY;
+        distances[last] = f64::INFINITY;
+
+        if range > 0. {
+            for window in order.windows(3) {
+                distances[window[1]] += (values[window[2]] - values[window[0]]) / range;
+            }
+        }
+    }
+
+    distances
+}
+
+/// Indexes ordered as in NSGA-II, by front and then by descending crowding distance
+pub(crate) fn order(objectives: &[&[f64]]) -> Vec<usize> {
+    fronts(objectives)
+        .into_iter()
+        .flat_map(|front| {
+            let distances = crowding_distances(objectives, &front);
+
+            let mut positions: Vec<usize> = (0..front.len()).collect();
+            positions.sort_by(|a, b| distances[*b].total_cmp(&distances[*a]));
+
+            positions.into_iter().map(move |position| front[position])
+        })
+        .collect()
+}
+
+/// Index of the front every objective vector belongs to
+pub(crate) fn ranks(objectives: &[&[f64]]) -> Vec<usize> {
+    let mut ranks = vec![0; objectives.len()];
+
+    for (rank, front) in fronts(objectives).into_iter().enumerate() {
+        for i in front {
+            ranks[i] = rank;
+        }
+    }
+
+    ranks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fronts_are_non_dominated() {
+        let objectives: Vec<&[f64]> = vec![&[1., 1.], &[3., 1.], &[2., 2.], &[1., 3.], &[0., 0.]];
+
+        assert_eq!(fronts(&objectives), vec![vec![1, 2, 3], vec![0], vec![4]]);
+        assert_eq!(ranks(&objectives), vec![1, 0, 0, 0, 2]);
+    }
+
+    #[test]
+    fn extremes_and_sparse_members_come_first() {
+        let objectives: Vec<&[f64]> = vec![&[2., 2.], &[0., 4.], &[1., 3.], &[4., 0.], &[0., 0.]];
+
+        // 0 and 2 share the first front with the extremes, 0 has more room around it
+        assert_eq!(order(&objectives), vec![1, 3, 0, 2, 4]);
+    }
+}
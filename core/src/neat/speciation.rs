@@ -1,29 +1,42 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::rc::Rc;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 
 use super::configuration::Configuration;
 use crate::genome::{Genome, GenomeId};
 
 /// Holds all genomes and species, does the process of speciation
 #[derive(Debug)]
+#[cfg_attr(
+    feature = "checkpoint",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub struct GenomeBank {
-    configuration: Rc<RefCell<Configuration>>,
-    genomes: HashMap<GenomeId, Genome>,
-    previous_genomes: HashMap<GenomeId, Genome>,
-    fitnesses: HashMap<GenomeId, f64>,
+    #[cfg_attr(feature = "checkpoint", serde(skip))]
+    configuration: Arc<Configuration>,
+    genomes: BTreeMap<GenomeId, Genome>,
+    previous_genomes: BTreeMap<GenomeId, Genome>,
+    fitnesses: BTreeMap<GenomeId, f64>,
+    scores: BTreeMap<GenomeId, f64>,
+    objectives: BTreeMap<GenomeId, Vec<f64>>,
 }
 
 impl GenomeBank {
-    pub fn new(configuration: Rc<RefCell<Configuration>>) -> Self {
+    pub fn new(configuration: Arc<Configuration>) -> Self {
         GenomeBank {
             configuration,
-            genomes: HashMap::new(),
-            previous_genomes: HashMap::new(),
-            fitnesses: HashMap::new(),
+            genomes: BTreeMap::new(),
+            previous_genomes: BTreeMap::new(),
+            fitnesses: BTreeMap::new(),
+            scores: BTreeMap::new(),
+            objectives: BTreeMap::new(),
         }
     }
 
+    /// Shares the configuration of the system, used when it changes or after restoring a checkpoint
+    pub(crate) fn set_configuration(&mut self, configuration: Arc<Configuration>) {
+        self.configuration = configuration;
+    }
+
     /// Adds a new genome
     pub fn add_genome(&mut self, genome: Genome) {
         self.genomes.insert(genome.id(), genome);
@@ -38,11 +51,11 @@ impl GenomeBank {
     }
 
     /// Returns a reference to the genomes
-    pub fn genomes(&self) -> &HashMap<GenomeId, Genome> {
+    pub fn genomes(&self) -> &BTreeMap<GenomeId, Genome> {
         &self.genomes
     }
 
-    pub fn previous_genomes(&self) -> &HashMap<GenomeId, Genome> {
+    pub fn previous_genomes(&self) -> &BTreeMap<GenomeId, Genome> {
         &self.previous_genomes
     }
 
@@ -52,9 +65,29 @@ impl GenomeBank {
     }
 
     /// Returns a reference to the fitnesses
-    pub fn fitnesses(&self) -> &HashMap<GenomeId, f64> {
+    pub fn fitnesses(&self) -> &BTreeMap<GenomeId, f64> {
         &self.fitnesses
     }
+
+    /// Tracks what selection uses for a particular genome, its fitness unless novelty is searched
+    pub fn mark_score(&mut self, genome_id: GenomeId, score: f64) {
+        self.scores.insert(genome_id, score);
+    }
+
+    /// Returns a reference to the selection scores
+    pub fn scores(&self) -> &BTreeMap<GenomeId, f64> {
+        &self.scores
+    }
+
+    /// Tracks the objectives of a particular genome in multi-objective evolution
+    pub fn mark_objectives(&mut self, genome_id: GenomeId, objectives: Vec<f64>) {
+        self.objectives.insert(genome_id, objectives);
+    }
+
+    /// Returns a reference to the objectives, empty unless there are several
+    pub fn objectives(&self) -> &BTreeMap<GenomeId, Vec<f64>> {
+        &self.objectives
+    }
 }
 
 #[cfg(test)]
@@ -63,7 +96,7 @@ mod tests {
 
     #[test]
     fn can_add_genome() {
-        let configuration: Rc<RefCell<Configuration>> = Default::default();
+        let configuration: Arc<Configuration> = Default::default();
         let mut bank = GenomeBank::new(configuration);
 
         let genome = Genome::new(1, 1);
@@ -72,7 +105,7 @@ mod tests {
 
     #[test]
     fn can_mark_fitness() {
-        let configuration: Rc<RefCell<Configuration>> = Default::default();
+        let configuration: Arc<Configuration> = Default::default();
         let mut bank = GenomeBank::new(configuration);
 
         let genome = Genome::new(1, 1);
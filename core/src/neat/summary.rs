@@ -0,0 +1,14 @@
+/// Statistics of a single generation, returned by `NEAT::step_generation`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenerationSummary {
+    pub generation: usize,
+    pub best_fitness: f64,
+    pub mean_fitness: f64,
+    pub population_size: usize,
+    pub species_count: usize,
+    /// Whether the best fitness reached the configured fitness goal
+    pub goal_reached: bool,
+    /// Why the checkpoint of this generation couldn't be written
+    #[cfg(feature = "checkpoint")]
+    pub checkpoint_error: Option<String>,
+}
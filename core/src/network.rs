@@ -1,166 +1,428 @@
+use num_traits::Float;
+
 use crate::activation::*;
-use crate::aggregations::aggregate;
+use crate::aggregations::{aggregate, Aggregation};
 use crate::connection::*;
 use crate::genome::Genome;
 use crate::node::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[cfg_attr(
     feature = "network-serde",
-    derive(serde::Serialize, serde::Deserialize)
+    derive(serde::Serialize, serde::Deserialize),
+    serde(
+        from = "SerializedNetwork<T>",
+        bound(
+            serialize = "T: serde::Serialize",
+            deserialize = "T: Float + serde::Deserialize<'de>"
+        )
+    )
 )]
-pub struct Network {
+pub struct Network<T = f64> {
     pub input_count: usize,
     pub output_count: usize,
-    pub nodes: Vec<Node>,
-    pub connections: Vec<Connection>,
+    pub nodes: Vec<Node<T>>,
+    pub connections: Vec<Connection<T>>,
     node_calculation_order: Vec<usize>,
+
+    // Incoming connections of node `i` are at `incoming_offsets[i]..incoming_offsets[i + 1]`
+    #[cfg_attr(feature = "network-serde", serde(skip))]
+    incoming_offsets: Vec<usize>,
+    #[cfg_attr(feature = "network-serde", serde(skip))]
+    incoming_sources: Vec<usize>,
+    #[cfg_attr(feature = "network-serde", serde(skip))]
+    incoming_weights: Vec<T>,
+
+    // Used by the `&mut self` evaluation methods
+    #[cfg_attr(feature = "network-serde", serde(skip))]
+    state: NetworkState<T>,
 }
 
-impl Network {
-    fn is_node_ready(&self, index: usize) -> bool {
-        let node = self.nodes.get(index).unwrap();
+/// The node values of one evaluation, networks are immutable while evaluating with a state of
+/// their own so one network can serve many episodes or threads at once
+#[derive(Debug, Clone)]
+pub struct NetworkState<T = f64> {
+    values: Vec<T>,
+    components: Vec<T>,
+    /// Values of the next step of `advance_with`, kept to reuse the allocation
+    next_values: Vec<T>,
+}
+
+impl<T> Default for NetworkState<T> {
+    fn default() -> Self {
+        NetworkState {
+            values: vec![],
+            components: vec![],
+            next_values: vec![],
+        }
+    }
+}
+
+impl<T: Float> NetworkState<T> {
+    /// Forgets the values of all nodes, recurrent networks start over from a blank state
+    pub fn reset(&mut self) {
+        self.values.iter_mut().for_each(|v| *v = T::zero());
+    }
+
+    /// The current value of every node
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+}
+
+/// Why a network couldn't produce outputs
+#[derive(Debug, Clone, PartialEq)]
+pub enum NetworkError {
+    /// The number of inputs differs from the number of input nodes
+    InputLengthMismatch { expected: usize, actual: usize },
+
+    /// An output is NaN or infinite
+    NonFiniteOutput { index: usize },
+}
+
+impl std::fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetworkError::InputLengthMismatch { expected, actual } => {
+                write!(f, "expected {} inputs, got {}", expected, actual)
+            }
+            NetworkError::NonFiniteOutput { index } => {
+                write!(f, "output {} is not finite", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NetworkError {}
+
+/// The serialized fields of a network, deserialized networks get compiled right away
+#[cfg(feature = "network-serde")]
+#[derive(serde::Deserialize)]
+struct SerializedNetwork<T> {
+    input_count: usize,
+    output_count: usize,
+    nodes: Vec<Node<T>>,
+    connections: Vec<Connection<T>>,
+    node_calculation_order: Vec<usize>,
+}
+
+#[cfg(feature = "network-serde")]
+impl<T: Float> From<SerializedNetwork<T>> for Network<T> {
+    fn from(n: SerializedNetwork<T>) -> Self {
+        Network::new(
+            n.input_count,
+            n.output_count,
+            n.nodes,
+            n.connections,
+            n.node_calculation_order,
+        )
+    }
+}
+
+impl<T: Float> Network<T> {
+    fn new(
+        input_count: usize,
+        output_count: usize,
+        nodes: Vec<Node<T>>,
+        connections: Vec<Connection<T>>,
+        node_calculation_order: Vec<usize>,
+    ) -> Self {
+        let mut network = Network {
+            input_count,
+            output_count,
+            nodes,
+            connections,
+            node_calculation_order,
+            incoming_offsets: vec![],
+            incoming_sources: vec![],
+            incoming_weights: vec![],
+            state: NetworkState::default(),
+        };
+        network.compile();
+
+        network
+    }
+
+    /// Rebuilds the incoming adjacency and buffers, needed after editing `nodes` or `connections`
+    pub fn compile(&mut self) {
+        let node_count = self.nodes.len();
 
-        let requirements_fullfilled = self.connections.iter().filter(|c| c.to == index).all(|c| {
-            let from_index = c.from;
-            let from_node = &self.nodes[from_index];
+        let mut incoming_counts = vec![0; node_count];
+        self.connections
+            .iter()
+            .for_each(|c| incoming_counts[c.to] += 1);
 
-            from_node.value.is_some()
+        self.incoming_offsets = Vec::with_capacity(node_count + 1);
+        self.incoming_offsets.push(0);
+        incoming_counts.iter().for_each(|count| {
+            let offset = *self.incoming_offsets.last().unwrap();
+            self.incoming_offsets.push(offset + count);
         });
-        let has_no_value = node.value.is_none();
 
-        requirements_fullfilled && has_no_value
+        // Fill every node's range in connection order
+        let mut next_slot = self.incoming_offsets.clone();
+        self.incoming_sources = vec![0; self.connections.len()];
+        self.incoming_weights = vec![T::zero(); self.connections.len()];
+        for c in &self.connections {
+            let slot = next_slot[c.to];
+            self.incoming_sources[slot] = c.from;
+            self.incoming_weights[slot] = c.weight;
+            next_slot[c.to] += 1;
+        }
+
+        self.state = self.new_state();
     }
 
-    pub fn forward_pass(&mut self, inputs: &Vec<f64>) -> Vec<f64> {
-        for i in &self.node_calculation_order {
-            let node = self.nodes.get(*i).unwrap();
+    /// A blank state sized for this network
+    pub fn new_state(&self) -> NetworkState<T> {
+        let most_incoming = self
+            .incoming_offsets
+            .windows(2)
+            .map(|w| w[1] - w[0])
+            .max()
+            .unwrap_or(0);
+
+        NetworkState {
+            values: vec![T::zero(); self.nodes.len()],
+            components: vec![T::zero(); most_incoming],
+            next_values: Vec::with_capacity(self.nodes.len()),
+        }
+    }
 
-            if matches!(node.kind, NodeKind::Input) {
-                self.nodes.get_mut(*i).unwrap().value = Some(*inputs.get(*i).unwrap());
+    /// Aggregated and biased input of a node, computed from the current values
+    fn node_input(&self, state: &mut NetworkState<T>, index: usize) -> T {
+        let node = &self.nodes[index];
+        let range = self.incoming_offsets[index]..self.incoming_offsets[index + 1];
+        let components = &mut state.components[..range.len()];
+        let values = &state.values;
+
+        components
+            .iter_mut()
+            .zip(&self.incoming_sources[range.clone()])
+            .zip(&self.incoming_weights[range])
+            .for_each(|((component, from), weight)| *component = values[*from] * *weight);
+
+        aggregate(&node.aggregation, components) + node.bias
+    }
+
+    /// Calculates the outputs into `outputs` without allocating, node values are kept in `state`
+    /// so recurrent connections see the previous pass
+    pub fn forward_pass_into_with(
+        &self,
+        state: &mut NetworkState<T>,
+        inputs: &[T],
+        outputs: &mut [T],
+    ) {
+        assert_eq!(
+            inputs.len(),
+            self.input_count,
+            "Inputs need to be of the same length as the number of input nodes"
+        );
+        assert_eq!(
+            outputs.len(),
+            self.output_count,
+            "Outputs need to be of the same length as the number of output nodes"
+        );
+
+        for &i in &self.node_calculation_order {
+            state.values[i] = if matches!(self.nodes[i].kind, NodeKind::Input) {
+                inputs[i]
             } else {
-                let components: Vec<f64> = self
-                    .connections
-                    .iter()
-                    .filter(|c| c.to == *i)
-                    .map(|c| {
-                        let incoming_value = self.nodes.get(c.from).unwrap().value.unwrap();
-                        incoming_value * c.weight
-                    })
-                    .collect();
-
-                let aggregated = aggregate(&node.aggregation, &components);
-                let aggregated_with_bias = aggregated + node.bias;
-
-                self.nodes.get_mut(*i).unwrap().value =
-                    Some(activate(aggregated_with_bias, &node.activation));
-            }
+                activate(self.node_input(state, i), &self.nodes[i].activation)
+            };
         }
 
+        self.write_outputs(state, outputs);
+    }
+
+    /// Calculates the outputs with the values kept in `state`
+    pub fn forward_pass_with(&self, state: &mut NetworkState<T>, inputs: &[T]) -> Vec<T> {
+        let mut outputs = vec![T::zero(); self.output_count];
+        self.forward_pass_into_with(state, inputs, &mut outputs);
+
+        outputs
+    }
+
+    fn write_outputs(&self, state: &NetworkState<T>, outputs: &mut [T]) {
         self.nodes
             .iter()
-            .filter(|n| matches!(n.kind, NodeKind::Output))
-            .map(|n| n.value.unwrap())
-            .collect()
+            .zip(&state.values)
+            .filter(|(n, _)| matches!(n.kind, NodeKind::Output))
+            .zip(outputs.iter_mut())
+            .for_each(|((_, value), output)| *output = *value);
+    }
+
+    /// Calculates the outputs into `outputs` without allocating, using the network's own state
+    pub fn forward_pass_into(&mut self, inputs: &[T], outputs: &mut [T]) {
+        let mut state = std::mem::take(&mut self.state);
+        self.forward_pass_into_with(&mut state, inputs, outputs);
+        self.state = state;
+    }
+
+    /// Calculates the outputs, node values are kept so recurrent connections see the previous pass
+    pub fn forward_pass(&mut self, inputs: &Vec<T>) -> Vec<T> {
+        let mut state = std::mem::take(&mut self.state);
+        let outputs = self.forward_pass_with(&mut state, inputs);
+        self.state = state;
+
+        outputs
+    }
+
+    /// Like `forward_pass`, but reports wrong sized inputs and outputs that blew up to NaN or
+    /// infinity instead of panicking or passing them on
+    pub fn try_forward_pass(&mut self, inputs: &[T]) -> Result<Vec<T>, NetworkError> {
+        if inputs.len() != self.input_count {
+            return Err(NetworkError::InputLengthMismatch {
+                expected: self.input_count,
+                actual: inputs.len(),
+            });
+        }
 
-        // let mut inputs_updated = false;
-        // let mut nodes_changed = -1;
-        // let mut nodes_changed_sum = 0;
+        let mut outputs = vec![T::zero(); self.output_count];
+        self.forward_pass_into(inputs, &mut outputs);
 
-        // while nodes_changed != 0 {
-        //     nodes_changed = 0;
+        match outputs.iter().position(|o| !o.is_finite()) {
+            Some(index) => Err(NetworkError::NonFiniteOutput { index }),
+            None => Ok(outputs),
+        }
+    }
 
-        //     // First pass, update inputs
-        //     if !inputs_updated {
-        //         self.nodes
-        //             .iter_mut()
-        //             .enumerate()
-        //             .filter(|(_, n)| matches!(n.kind, NodeKind::Input))
-        //             .for_each(|(i, n)| {
-        //                 let input_value = *inputs.get(i).expect(
-        //                     "Inputs need to be of the same length as the number of input nodes",
-        //                 );
+    /// Evaluates `n_rows` input rows stored one after another and returns the outputs row after
+    /// row. Every row starts from a blank state, so recurrent connections contribute nothing
+    pub fn forward_batch(&self, inputs: &[T], n_rows: usize) -> Vec<T> {
+        assert_eq!(inputs.len(), n_rows * self.input_count);
 
-        //                 n.value = Some(input_value);
-        //                 nodes_changed += 1;
-        //             });
+        // Node major, the values of a node for the whole batch are next to each other
+        let mut values = vec![T::zero(); self.nodes.len() * n_rows];
+        let mut column = vec![T::zero(); n_rows];
+        let mut components = self.new_state().components;
 
-        //         inputs_updated = true;
-        //     }
+        for &i in &self.node_calculation_order {
+            let node = &self.nodes[i];
 
-        //     // Other passes, update non input nodes
-        //     let mut node_updates: Vec<(usize, f64)> = vec![];
-        //     self.nodes
-        //         .iter()
-        //         .enumerate()
-        //         .filter(|(i, n)| {
-        //             let is_not_input = !matches!(n.kind, NodeKind::Input);
-        //             let is_ready = self.is_node_ready(*i);
+            if matches!(node.kind, NodeKind::Input) {
+                column
+                    .iter_mut()
+                    .enumerate()
+                    .for_each(|(row, v)| *v = inputs[row * self.input_count + i]);
+            } else {
+                let range = self.incoming_offsets[i]..self.incoming_offsets[i + 1];
+
+                if matches!(node.aggregation, Aggregation::Sum) {
+                    // Sums are accumulated a whole column at a time
+                    column.iter_mut().for_each(|v| *v = T::zero());
+
+                    for k in range {
+                        let from = self.incoming_sources[k];
+                        let weight = self.incoming_weights[k];
+
+                        column
+                            .iter_mut()
+                            .zip(&values[from * n_rows..(from + 1) * n_rows])
+                            .for_each(|(v, from_value)| *v = *v + *from_value * weight);
+                    }
+                } else {
+                    let components = &mut components[..range.len()];
+
+                    for (row, v) in column.iter_mut().enumerate() {
+                        components
+                            .iter_mut()
+                            .zip(range.clone())
+                            .for_each(|(component, k)| {
+                                *component = values[self.incoming_sources[k] * n_rows + row]
+                                    * self.incoming_weights[k]
+                            });
+
+                        *v = aggregate(&node.aggregation, components);
+                    }
+                }
+
+                column
+                    .iter_mut()
+                    .for_each(|v| *v = activate(*v + node.bias, &node.activation));
+            }
 
-        //             is_not_input && is_ready
-        //         })
-        //         .for_each(|(i, n)| {
-        //             let incoming_connections: Vec<&Connection> =
-        //                 self.connections.iter().filter(|c| c.to == i).collect();
+            values[i * n_rows..(i + 1) * n_rows].copy_from_slice(&column);
+        }
 
-        //             let mut value = 0.;
+        let output_indexes: Vec<usize> = (0..self.nodes.len())
+            .filter(|i| matches!(self.nodes[*i].kind, NodeKind::Output))
+            .collect();
 
-        //             for c in incoming_connections {
-        //                 let from_node = self.nodes.get(c.from).unwrap();
-        //                 value += from_node.value.unwrap() * c.weight;
-        //             }
+        (0..n_rows)
+            .flat_map(|row| output_indexes.iter().map(move |o| (row, *o)))
+            .map(|(row, o)| values[o * n_rows + row])
+            .collect()
+    }
 
-        //             value += n.bias;
+    /// Integrates the network as a CTRNN for `dt`, every node follows `dy/dt = (-y + f(sum)) / tau`
+    pub fn advance_with(&self, state: &mut NetworkState<T>, inputs: &[T], dt: T) -> Vec<T> {
+        assert_eq!(
+            inputs.len(),
+            self.input_count,
+            "Inputs need to be of the same length as the number of input nodes"
+        );
 
-        //             node_updates.push((i, value));
-        //         });
+        for (i, node) in self.nodes.iter().enumerate() {
+            if matches!(node.kind, NodeKind::Input) {
+                state.values[i] = inputs[i];
+            }
+        }
 
-        //     node_updates.iter().for_each(|(i, v)| {
-        //         let n = self.nodes.get_mut(*i).unwrap();
+        // All nodes are updated at once from the values of the previous step
+        let mut next_values = std::mem::take(&mut state.next_values);
+        next_values.clear();
+        next_values.extend((0..self.nodes.len()).map(|i| {
+            let value = state.values[i];
+            if matches!(self.nodes[i].kind, NodeKind::Input) {
+                return value;
+            }
 
-        //         n.value = Some(activate(*v, &n.activation));
+            let activated = activate(self.node_input(state, i), &self.nodes[i].activation);
+            value + dt / self.nodes[i].time_constant * (activated - value)
+        }));
 
-        //         nodes_changed += 1;
-        //     });
+        state.next_values = std::mem::replace(&mut state.values, next_values);
 
-        //     nodes_changed_sum += nodes_changed;
-        // }
+        let mut outputs = vec![T::zero(); self.output_count];
+        self.write_outputs(state, &mut outputs);
 
-        // let outputs = self
-        //     .nodes
-        //     .iter()
-        //     .filter(|n| matches!(n.kind, NodeKind::Output))
-        //     .map(|n| n.value.unwrap())
-        //     .collect();
+        outputs
+    }
 
-        // // Very important, I forgot this initially :facepalm:
-        // self.clear_values();
+    /// Like `advance_with`, using the network's own state
+    pub fn advance(&mut self, inputs: &[T], dt: T) -> Vec<T> {
+        let mut state = std::mem::take(&mut self.state);
+        let outputs = self.advance_with(&mut state, inputs, dt);
+        self.state = state;
 
-        // outputs
+        outputs
     }
 
-    fn clear_values(&mut self) {
-        self.nodes.iter_mut().for_each(|n| n.value = None);
+    /// Forgets the values of all nodes, recurrent networks start over from a blank state
+    pub fn reset_state(&mut self) {
+        self.state.reset();
     }
 }
 
-impl From<&Genome> for Network {
+/// Genomes always hold `f64`, the network converts them to the requested precision
+impl<T: Float> From<&Genome> for Network<T> {
     fn from(g: &Genome) -> Self {
-        let nodes: Vec<Node> = g.nodes().iter().map(From::from).collect();
-        let connections: Vec<Connection> = g
+        let nodes: Vec<Node<T>> = g.nodes().iter().map(From::from).collect();
+        let connections: Vec<Connection<T>> = g
             .connections()
             .iter()
             .filter(|c| !c.disabled)
             .map(From::from)
             .collect();
 
-        Network {
-            input_count: g.input_count(),
-            output_count: g.output_count(),
+        Network::new(
+            g.input_count(),
+            g.output_count(),
             nodes,
             connections,
-            node_calculation_order: g.node_order().unwrap(),
-        }
+            g.node_order().unwrap_or_else(|| g.recurrent_node_order()),
+        )
     }
 }
 
@@ -171,7 +433,7 @@ mod tests {
     #[test]
     fn init_network() {
         let g = Genome::new(1, 1);
-        Network::from(&g);
+        Network::<f64>::from(&g);
     }
 
     #[test]
@@ -182,9 +444,186 @@ mod tests {
         let inputs: Vec<Vec<f64>> = vec![vec![0., 0.], vec![0., 1.], vec![1., 0.], vec![1., 1.]];
 
         for i in inputs {
-            let o = n.forward_pass(i.clone());
+            let o = n.forward_pass(&i);
 
             dbg!(i, o);
         }
     }
+
+    #[test]
+    fn forward_pass_into_matches_forward_pass() {
+        let mut rng = rand::thread_rng();
+        let mut g = Genome::new(3, 2);
+
+        g.add_node(&mut rng);
+        g.add_node(&mut rng);
+        g.node_mut(5).unwrap().aggregation = crate::aggregations::Aggregation::Median;
+
+        let mut allocating = Network::from(&g);
+        let mut compiled = Network::from(&g);
+        let mut outputs = [0.; 2];
+
+        for inputs in &[vec![0.5, -1., 2.], vec![1., 1., 1.], vec![-3., 0., 0.25]] {
+            compiled.forward_pass_into(inputs, &mut outputs);
+            assert_eq!(allocating.forward_pass(inputs), outputs.to_vec());
+        }
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Outputs need to be of the same length as the number of output nodes"
+    )]
+    fn forward_pass_into_checks_outputs() {
+        let mut n = Network::from(&Genome::new(3, 2));
+
+        n.forward_pass_into(&[0., 0., 0.], &mut [0.]);
+    }
+
+    #[test]
+    fn try_forward_pass_reports_errors() {
+        let mut g = Genome::new(2, 1);
+        g.node_mut(2).unwrap().activation = ActivationKind::Identity;
+        g.node_mut(2).unwrap().aggregation = crate::aggregations::Aggregation::Sum;
+
+        let mut n = Network::from(&g);
+
+        assert_eq!(
+            n.try_forward_pass(&[1.]),
+            Err(NetworkError::InputLengthMismatch {
+                expected: 2,
+                actual: 1
+            })
+        );
+        assert_eq!(
+            n.try_forward_pass(&[f64::NAN, 1.]),
+            Err(NetworkError::NonFiniteOutput { index: 0 })
+        );
+        assert!(n.try_forward_pass(&[1., 1.]).is_ok());
+    }
+
+    #[test]
+    fn forward_batch_matches_forward_pass() {
+        let mut rng = rand::thread_rng();
+        let mut g = Genome::new(3, 2);
+
+        g.add_node(&mut rng);
+        g.add_node(&mut rng);
+        g.node_mut(5).unwrap().aggregation = crate::aggregations::Aggregation::Median;
+        g.node_mut(6).unwrap().aggregation = crate::aggregations::Aggregation::Sum;
+
+        let mut n = Network::from(&g);
+        let rows = [vec![0.5, -1., 2.], vec![1., 1., 1.], vec![-3., 0., 0.25]];
+
+        let batch_outputs = n.forward_batch(&rows.concat(), rows.len());
+        let single_outputs: Vec<f64> = rows.iter().flat_map(|r| n.forward_pass(r)).collect();
+
+        assert_eq!(batch_outputs, single_outputs);
+    }
+
+    #[test]
+    fn f32_network_matches_f64_network() {
+        let g = Genome::new(3, 2);
+
+        let mut n64: Network<f64> = Network::from(&g);
+        let mut n32: Network<f32> = Network::from(&g);
+
+        let outputs64 = n64.forward_pass(&vec![0.5, -1., 2.]);
+        let outputs32 = n32.forward_pass(&vec![0.5, -1., 2.]);
+
+        outputs64
+            .iter()
+            .zip(outputs32)
+            .for_each(|(o64, o32)| assert!((*o64 as f32 - o32).abs() < 1e-4));
+    }
+
+    #[test]
+    fn recurrent_network_keeps_state() {
+        let mut rng = rand::thread_rng();
+        let mut g = Genome::new(1, 1);
+
+        g.add_recurrent_connection(1, 1, &mut rng).unwrap();
+        g.node_mut(1).unwrap().activation = ActivationKind::Identity;
+        g.node_mut(1).unwrap().aggregation = crate::aggregations::Aggregation::Sum;
+        g.node_mut(1).unwrap().bias = 0.;
+        g.connection_mut(0).unwrap().weight = 1.;
+        g.connection_mut(1).unwrap().weight = 1.;
+
+        let mut n = Network::from(&g);
+
+        // The output accumulates every input it has seen
+        assert_eq!(n.forward_pass(&vec![1.]), vec![1.]);
+        assert_eq!(n.forward_pass(&vec![1.]), vec![2.]);
+        assert_eq!(n.forward_pass(&vec![0.]), vec![2.]);
+
+        n.reset_state();
+        assert_eq!(n.forward_pass(&vec![1.]), vec![1.]);
+    }
+
+    #[test]
+    fn shared_network_with_separate_states() {
+        use rayon::prelude::*;
+
+        fn assert_send_sync<S: Send + Sync>(_: &S) {}
+
+        let mut rng = rand::thread_rng();
+        let mut g = Genome::new(1, 1);
+
+        g.add_recurrent_connection(1, 1, &mut rng).unwrap();
+        g.node_mut(1).unwrap().activation = ActivationKind::Identity;
+        g.node_mut(1).unwrap().aggregation = crate::aggregations::Aggregation::Sum;
+        g.node_mut(1).unwrap().bias = 0.;
+        g.connection_mut(0).unwrap().weight = 1.;
+        g.connection_mut(1).unwrap().weight = 1.;
+
+        let n: Network = Network::from(&g);
+        assert_send_sync(&n);
+
+        let accumulated: Vec<f64> = (1..=3)
+            .into_par_iter()
+            .map(|input| {
+                let mut state = n.new_state();
+                (0..4)
+                    .map(|_| n.forward_pass_with(&mut state, &[input as f64])[0])
+                    .last()
+                    .unwrap()
+            })
+            .collect();
+
+        // Every episode accumulated only its own inputs
+        assert_eq!(accumulated, vec![4., 8., 12.]);
+
+        let mut copy = n.clone();
+        assert_eq!(copy.forward_pass(&vec![1.]), vec![1.]);
+    }
+
+    #[test]
+    fn advance_approaches_activation() {
+        let mut g = Genome::new(1, 1);
+
+        let output = g.node_mut(1).unwrap();
+        output.activation = ActivationKind::Identity;
+        output.aggregation = crate::aggregations::Aggregation::Sum;
+        output.bias = 0.;
+        output.time_constant = 2.;
+        g.connection_mut(0).unwrap().weight = 1.;
+
+        let mut n = Network::from(&g);
+
+        // Half of the remaining distance to the target is covered with dt equal to half of tau
+        assert_eq!(n.advance(&[1.], 1.), vec![0.5]);
+        assert_eq!(n.advance(&[1.], 1.), vec![0.75]);
+
+        for _ in 0..100 {
+            n.advance(&[1.], 1.);
+        }
+        assert!((n.advance(&[1.], 1.)[0] - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    #[should_panic(expected = "Inputs need to be of the same length as the number of input nodes")]
+    fn advance_checks_inputs() {
+        let mut n = Network::from(&Genome::new(3, 1));
+
+        n.advance(&[0., 0.], 1.);
+    }
 }
@@ -1,10 +1,12 @@
-use crate::activation::ActivationKind;
+use num_traits::Float;
+
+use crate::activation::{constant, ActivationKind};
 use crate::aggregations::Aggregation;
 use crate::genome::node::NodeGene;
 
 #[derive(Debug, Clone, PartialEq, Hash)]
 #[cfg_attr(
-    feature = "network-serde",
+    any(feature = "network-serde", feature = "genome-serde"),
     derive(serde::Serialize, serde::Deserialize)
 )]
 pub enum NodeKind {
@@ -14,26 +16,26 @@ pub enum NodeKind {
     Constant,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[cfg_attr(
     feature = "network-serde",
     derive(serde::Serialize, serde::Deserialize)
 )]
-pub struct Node {
+pub struct Node<T = f64> {
     pub kind: NodeKind,
     pub aggregation: Aggregation,
     pub activation: ActivationKind,
-    pub bias: f64,
-    pub value: Option<f64>,
+    pub bias: T,
+    pub time_constant: T,
 }
 
-impl From<&NodeGene> for Node {
+impl<T: Float> From<&NodeGene> for Node<T> {
     fn from(g: &NodeGene) -> Self {
         Node {
             kind: g.kind.clone(),
             activation: g.activation.clone(),
-            bias: g.bias,
-            value: None,
+            bias: constant(g.bias),
+            time_constant: constant(g.time_constant),
             aggregation: g.aggregation.clone(),
         }
     }
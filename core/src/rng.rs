@@ -0,0 +1,43 @@
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64;
+
+/// The random number generator behind every decision of the evolution process
+pub type NeatRng = Pcg64;
+
+/// Creates a generator from the seed, or from system entropy if there is no seed
+pub fn from_seed(seed: Option<u64>) -> NeatRng {
+    match seed {
+        Some(seed) => NeatRng::seed_from_u64(seed),
+        None => NeatRng::from_entropy(),
+    }
+}
+
+/// Derives an independent generator, so work handed to rayon stays reproducible no matter which
+/// thread ends up running it
+pub fn split<R: Rng + ?Sized>(rng: &mut R) -> NeatRng {
+    NeatRng::seed_from_u64(rng.gen())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_numbers() {
+        let mut a = from_seed(Some(1337));
+        let mut b = from_seed(Some(1337));
+
+        let numbers_a: Vec<u64> = (0..10).map(|_| a.gen()).collect();
+        let numbers_b: Vec<u64> = (0..10).map(|_| b.gen()).collect();
+
+        assert_eq!(numbers_a, numbers_b);
+    }
+
+    #[test]
+    fn split_is_deterministic() {
+        let mut a = from_seed(Some(1337));
+        let mut b = from_seed(Some(1337));
+
+        assert_eq!(split(&mut a).gen::<u64>(), split(&mut b).gen::<u64>());
+    }
+}
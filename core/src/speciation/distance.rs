@@ -1,18 +1,17 @@
-use std::cell::RefCell;
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::Configuration;
-use crate::{ConnectionGene, Genome};
+use crate::{ConnectionGene, Genome, NodeGene};
 
 type DistanceKey = String;
 pub struct GenomicDistanceCache {
-    configuration: Rc<RefCell<Configuration>>,
+    configuration: Arc<Configuration>,
     cache: HashMap<DistanceKey, f64>,
 }
 
 impl GenomicDistanceCache {
-    pub fn new(configuration: Rc<RefCell<Configuration>>) -> Self {
+    pub fn new(configuration: Arc<Configuration>) -> Self {
         GenomicDistanceCache {
             configuration,
             cache: HashMap::new(),
@@ -40,8 +39,9 @@ impl GenomicDistanceCache {
             distance_node_bias_coefficient,
             distance_node_activation_coefficient,
             distance_node_aggregation_coefficient,
+            distance_node_time_constant_coefficient,
         ) = {
-            let conf = self.configuration.borrow();
+            let conf = &self.configuration;
 
             (
                 conf.distance_connection_disjoint_coefficient,
@@ -50,6 +50,7 @@ impl GenomicDistanceCache {
                 conf.distance_node_bias_coefficient,
                 conf.distance_node_activation_coefficient,
                 conf.distance_node_aggregation_coefficient,
+                conf.distance_node_time_constant_coefficient,
             )
         };
 
@@ -58,52 +59,27 @@ impl GenomicDistanceCache {
         let max_connection_genes = usize::max(a.connections().len(), b.connections().len());
         let max_node_genes = usize::max(a.nodes().len(), b.nodes().len());
 
-        let mut disjoint_connections: Vec<&ConnectionGene> = vec![];
-        let mut common_connections: Vec<(&ConnectionGene, &ConnectionGene)> = vec![];
+        // Genes are matched by their historical markings
+        let connections_b: HashMap<usize, &ConnectionGene> = b
+            .connections()
+            .iter()
+            .map(|connection| (connection.innovation, connection))
+            .collect();
 
-        let mut disjoint_map: HashMap<usize, bool> = HashMap::new();
-        a.connections()
+        let common_connections: Vec<(&ConnectionGene, &ConnectionGene)> = a
+            .connections()
             .iter()
-            .chain(b.connections().iter())
-            .map(|connection| connection.innovation_number())
-            .for_each(|innovation_number| {
-                if let Some(is_disjoint) = disjoint_map.get_mut(&innovation_number) {
-                    *is_disjoint = false;
-                } else {
-                    disjoint_map.insert(innovation_number, true);
-                }
-            });
-
-        disjoint_map
-            .into_iter()
-            .for_each(|(innovation_number, is_disjoint)| {
-                if is_disjoint {
-                    let disjoint_connection = a
-                        .connections()
-                        .iter()
-                        .chain(b.connections().iter())
-                        .find(|connection| connection.innovation_number() == innovation_number)
-                        .unwrap();
-
-                    disjoint_connections.push(disjoint_connection);
-                } else {
-                    let common_connection_a = a
-                        .connections()
-                        .iter()
-                        .find(|connection| connection.innovation_number() == innovation_number)
-                        .unwrap();
-                    let common_connection_b = b
-                        .connections()
-                        .iter()
-                        .find(|connection| connection.innovation_number() == innovation_number)
-                        .unwrap();
-
-                    common_connections.push((common_connection_a, common_connection_b));
-                }
-            });
+            .filter_map(|connection_a| {
+                connections_b
+                    .get(&connection_a.innovation)
+                    .map(|connection_b| (connection_a, *connection_b))
+            })
+            .collect();
+        let disjoint_connections_count =
+            a.connections().len() + b.connections().len() - 2 * common_connections.len();
 
         let disjoint_factor =
-            disjoint_connections.len() as f64 * distance_connection_disjoint_coefficient;
+            disjoint_connections_count as f64 * distance_connection_disjoint_coefficient;
 
         let connections_difference_factor: f64 = common_connections
             .iter()
@@ -121,10 +97,20 @@ impl GenomicDistanceCache {
             })
             .sum::<f64>();
 
+        let nodes_b: HashMap<usize, &NodeGene> = b
+            .nodes()
+            .iter()
+            .map(|node| (node.innovation, node))
+            .collect();
+
         let nodes_difference_factor: f64 = a
             .nodes()
             .iter()
-            .zip(b.nodes())
+            .filter_map(|node_a| {
+                nodes_b
+                    .get(&node_a.innovation)
+                    .map(|node_b| (node_a, *node_b))
+            })
             .map(|(node_a, node_b)| {
                 let mut node_distance = 0.;
 
@@ -137,13 +123,17 @@ impl GenomicDistanceCache {
                 }
 
                 node_distance += (node_a.bias - node_b.bias).abs() * distance_node_bias_coefficient;
+                node_distance += (node_a.time_constant - node_b.time_constant).abs()
+                    * distance_node_time_constant_coefficient;
 
                 node_distance
             })
             .sum();
 
         distance += nodes_difference_factor;
-        distance += (connections_difference_factor + disjoint_factor) / max_connection_genes as f64;
+        // Genomes without any connections don't differ in them
+        distance += (connections_difference_factor + disjoint_factor)
+            / usize::max(max_connection_genes, 1) as f64;
 
         distance
     }
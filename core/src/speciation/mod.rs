@@ -1,7 +1,6 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::collections::HashSet;
-use std::rc::Rc;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::sync::Arc;
 
 use crate::Configuration;
 use crate::{Genome, GenomeId};
@@ -10,22 +9,32 @@ use distance::GenomicDistanceCache;
 
 mod distance;
 
+#[cfg_attr(
+    feature = "checkpoint",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub struct SpeciesSet {
-    configuration: Rc<RefCell<Configuration>>,
+    #[cfg_attr(feature = "checkpoint", serde(skip))]
+    configuration: Arc<Configuration>,
     last_index: Option<usize>,
-    species: HashMap<usize, Species>,
+    species: BTreeMap<usize, Species>,
 }
 
 impl SpeciesSet {
-    pub fn new(configuration: Rc<RefCell<Configuration>>) -> Self {
+    pub fn new(configuration: Arc<Configuration>) -> Self {
         SpeciesSet {
             configuration,
             last_index: None,
-            species: HashMap::new(),
+            species: BTreeMap::new(),
         }
     }
 
-    pub fn species(&self) -> &HashMap<usize, Species> {
+    /// Shares the configuration of the system, used when it changes or after restoring a checkpoint
+    pub(crate) fn set_configuration(&mut self, configuration: Arc<Configuration>) {
+        self.configuration = configuration;
+    }
+
+    pub fn species(&self) -> &BTreeMap<usize, Species> {
         &self.species
     }
 
@@ -33,11 +42,11 @@ impl SpeciesSet {
         &mut self,
         generation: usize,
         current_genomes: &[GenomeId],
-        all_genomes: &HashMap<GenomeId, Genome>,
-        fitnesses: &HashMap<GenomeId, f64>,
+        all_genomes: &BTreeMap<GenomeId, Genome>,
+        fitnesses: &BTreeMap<GenomeId, f64>,
     ) {
         let (compatibility_threshold, stagnation_after, elitism_species) = {
-            let config = self.configuration.borrow();
+            let config = &self.configuration;
 
             (
                 config.compatibility_threshold,
@@ -48,8 +57,8 @@ impl SpeciesSet {
 
         let mut distances = GenomicDistanceCache::new(self.configuration.clone());
 
-        let mut unspeciated_genomes: HashSet<GenomeId> = current_genomes.iter().cloned().collect();
-        let mut new_species: HashMap<usize, Species> = self.species.clone();
+        let mut unspeciated_genomes: BTreeSet<GenomeId> = current_genomes.iter().cloned().collect();
+        let mut new_species: BTreeMap<usize, Species> = self.species.clone();
 
         // Find new representatives for existing species
         self.species.iter().for_each(|(species_id, species)| {
@@ -185,19 +194,11 @@ impl SpeciesSet {
             .map(|(id, species)| (*id, species.adjusted_fitness.unwrap()))
             .collect();
 
-        stagnated_ids_and_adjusted_fitnesses.sort_by(|a, b| {
-            use std::cmp::Ordering::*;
-
-            if a.1 > b.1 {
-                Less
-            } else {
-                Greater
-            }
-        });
+        stagnated_ids_and_adjusted_fitnesses.sort_by(|a, b| b.1.total_cmp(&a.1));
 
         stagnated_ids_and_adjusted_fitnesses
             .iter()
-            .take(usize::max(new_species.len() - elitism_species, 0))
+            .take(new_species.len().saturating_sub(elitism_species))
             .for_each(|(id, _)| {
                 new_species.remove(id).unwrap();
             });
@@ -208,6 +209,10 @@ impl SpeciesSet {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "checkpoint",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub struct Species {
     created: usize,
 
@@ -106,6 +106,11 @@ impl CartPole {
     pub fn apply_force_to_pole(&mut self, force: f64) {
         self.dtheta += force;
     }
+
+    /// Partially observable state, the cart position and pole angle without their velocities
+    pub fn positions(&self) -> [f64; 2] {
+        [self.x, self.theta]
+    }
 }
 
 impl Environment for CartPole {
@@ -1,6 +1,7 @@
 use rand::random;
+use std::collections::HashMap;
 
-use neat_core::{Configuration, Network, NEAT};
+use neat_core::{Configuration, GenomeId, Network, NEAT};
 use neat_environment::Environment;
 
 #[derive(Clone, Copy, Debug)]
@@ -114,31 +115,33 @@ impl TicTacToe {
     }
 
     fn did_mark_win(&self, check_mark: Mark) -> bool {
-        let winning_lines = [
-            [0, 1, 2],
-            [3, 4, 5],
-            [6, 7, 8],
-            [0, 3, 6],
-            [1, 4, 7],
-            [2, 5, 8],
-            [0, 4, 8],
-            [2, 4, 6],
-        ];
-
-        let external_won = winning_lines.iter().any(|line| {
-            line.iter()
-                .map(|mark_index| self.field.get(*mark_index).unwrap())
-                .all(|mark| match (mark, check_mark) {
-                    (Mark::X, Mark::X) => true,
-                    (Mark::O, Mark::O) => true,
-                    _ => false,
-                })
-        });
-
-        external_won
+        did_mark_win(&self.field, check_mark)
     }
 }
 
+fn did_mark_win(field: &Field, check_mark: Mark) -> bool {
+    let winning_lines = [
+        [0, 1, 2],
+        [3, 4, 5],
+        [6, 7, 8],
+        [0, 3, 6],
+        [1, 4, 7],
+        [2, 5, 8],
+        [0, 4, 8],
+        [2, 4, 6],
+    ];
+
+    winning_lines.iter().any(|line| {
+        line.iter()
+            .map(|mark_index| field.get(*mark_index).unwrap())
+            .all(|mark| match (mark, check_mark) {
+                (Mark::X, Mark::X) => true,
+                (Mark::O, Mark::O) => true,
+                _ => false,
+            })
+    })
+}
+
 impl Environment for TicTacToe {
     type State = Field;
     type Input = usize;
@@ -210,9 +213,11 @@ impl Environment for TicTacToe {
 }
 
 fn state_to_inputs(env: &TicTacToe) -> Vec<f64> {
-    let player_mark = env.external_mark();
+    field_to_inputs(&env.state(), env.external_mark())
+}
 
-    env.state()
+fn field_to_inputs(field: &Field, player_mark: Mark) -> Vec<f64> {
+    field
         .iter()
         .map(|mark| match (player_mark, *mark) {
             (Mark::X, Mark::X) => 1.,
@@ -238,6 +243,65 @@ fn move_from_outputs(outputs: &[f64]) -> usize {
         .0
 }
 
+/// Plays a game between two networks and returns the points of both, a win is worth 1 point and a
+/// draw is worth half a point, placing a mark on an occupied field loses the game
+fn play_match(x_player: &mut Network, o_player: &mut Network) -> (f64, f64) {
+    let mut field: Field = [Mark::Empty; 9];
+
+    for turn in 0..9 {
+        let (player, mark) = if turn % 2 == 0 {
+            (&mut *x_player, Mark::X)
+        } else {
+            (&mut *o_player, Mark::O)
+        };
+
+        let inputs = field_to_inputs(&field, mark);
+        let outputs: Vec<f64> = player.forward_pass(&inputs);
+        let field_index = move_from_outputs(&outputs);
+
+        if !matches!(field[field_index], Mark::Empty) {
+            return if turn % 2 == 0 { (0., 1.) } else { (1., 0.) };
+        }
+
+        field[field_index] = mark;
+
+        if did_mark_win(&field, mark) {
+            return if turn % 2 == 0 { (1., 0.) } else { (0., 1.) };
+        }
+    }
+
+    (0.5, 0.5)
+}
+
+/// Every network plays against every other network, once as X and once as O
+fn round_robin(population: &mut [(GenomeId, Network)]) -> HashMap<GenomeId, f64> {
+    let mut points = vec![0.; population.len()];
+
+    for first in 0..population.len() {
+        for second in first + 1..population.len() {
+            let (head, tail) = population.split_at_mut(second);
+            let first_network = &mut head[first].1;
+            let second_network = &mut tail[0].1;
+
+            let (first_points, second_points) = play_match(first_network, second_network);
+            points[first] += first_points;
+            points[second] += second_points;
+
+            let (second_points, first_points) = play_match(second_network, first_network);
+            points[first] += first_points;
+            points[second] += second_points;
+        }
+    }
+
+    let games_per_network = usize::max(2 * population.len().saturating_sub(1), 1) as f64;
+
+    population
+        .iter()
+        .zip(points)
+        .map(|((genome_id, _), genome_points)| (*genome_id, genome_points / games_per_network))
+        .collect()
+}
+
 fn play_network(network: &mut Network) {
     println!("Playing...");
 
@@ -262,46 +326,20 @@ fn play_network(network: &mut Network) {
         }
     }
 
-    println!("Game over, last state");
+    let outcome = if env.did_external_win() {
+        "network won"
+    } else if env.is_draw() {
+        "draw"
+    } else {
+        "network lost"
+    };
+
+    println!("Game over ({}), last state", outcome);
     env.render();
 }
 
 fn main() {
-    let mut system = NEAT::new(9, 9, |network| {
-        let games = 100;
-        let mut turns = 0;
-        let mut games_won = 0;
-        let mut games_draw = 0;
-
-        let mut env = TicTacToe::new();
-
-        for _ in 0..games {
-            env.reset();
-
-            loop {
-                if env.game_over() {
-                    break;
-                }
-
-                let inputs = state_to_inputs(&env);
-                let outputs: Vec<f64> = network.forward_pass(&inputs.clone());
-                let max_output_index: usize = move_from_outputs(&outputs);
-
-                if env.step(max_output_index).is_ok() {
-                    turns += 1;
-                } else {
-                    break;
-                }
-            }
-
-            games_won += if env.did_external_win() { 1 } else { 0 };
-            games_draw += if env.is_draw() { 1 } else { 0 };
-        }
-
-        // games as f64 / (games_won as f64 + games_draw as f64) //+ turns as f64 * 0.01
-        // turns as f64 / games as f64
-        (games_won as f64 + games_draw as f64) / games as f64
-    });
+    let mut system = NEAT::with_population_fitness(9, 9, round_robin);
 
     system.set_configuration(Configuration {
         population_size: 50,
@@ -357,4 +395,28 @@ mod tests {
         env.render();
         env.reset();
     }
+
+    #[test]
+    fn round_robin_scores_everyone() {
+        use neat_core::Genome;
+
+        let mut population: Vec<(GenomeId, Network)> = (0..4)
+            .map(|_| {
+                let genome = Genome::new(9, 9);
+                (genome.id(), Network::from(&genome))
+            })
+            .collect();
+
+        let fitnesses = round_robin(&mut population);
+        let total_points: f64 = fitnesses.values().sum::<f64>() * 6.;
+
+        assert_eq!(fitnesses.len(), 4);
+        // Every one of the 12 games hands out exactly one point
+        assert!((total_points - 12.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn round_robin_of_nobody() {
+        assert!(round_robin(&mut []).is_empty());
+    }
 }
@@ -4,8 +4,11 @@ use neat_export::to_file;
 
 mod gui;
 
-fn train() {
-    let mut system = NEAT::new(4, 1, |network| {
+/// With `positions_only` the networks don't see velocities and need recurrent connections
+fn train(positions_only: bool) {
+    let inputs = if positions_only { 2 } else { 4 };
+
+    let mut system = NEAT::new(inputs, 1, move |network| {
         let num_simulations = 10;
         let max_steps = 1000;
         let mut env = CartPole::new();
@@ -15,14 +18,19 @@ fn train() {
 
         for _ in 0..num_simulations {
             env.reset();
+            network.reset_state();
 
             for _ in 0..max_steps {
                 if env.done() {
                     break;
                 }
 
-                let state = env.state();
-                let network_output = network.forward_pass(&state.to_vec());
+                let state = if positions_only {
+                    env.positions().to_vec()
+                } else {
+                    env.state().to_vec()
+                };
+                let network_output = network.forward_pass(&state);
                 let env_input = f64::max(-1., f64::min(1., *network_output.first().unwrap()));
 
                 env.step(env_input).unwrap();
@@ -42,6 +50,7 @@ fn train() {
         node_cost: 1.,
         connection_cost: 1.,
         compatibility_threshold: 2.,
+        recurrent_connections: positions_only,
         ..Default::default()
     });
 
@@ -70,7 +79,11 @@ fn main() {
     let param: String = std::env::args().skip(1).take(1).collect();
 
     if param == "train" {
-        train();
+        train(false);
+    };
+
+    if param == "train-positions" {
+        train(true);
     };
 
     if param == "visualize" {
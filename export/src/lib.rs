@@ -1,27 +1,47 @@
-use neat_core::Network;
+use neat_core::{Float, Genome, Network};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use std::fs::{read, write};
 use std::path::Path;
 
-pub fn to_bytes(network: &Network) -> Vec<u8> {
+/// Works for both precisions, `f32` networks take about half the space
+pub fn to_bytes<T: Serialize>(network: &Network<T>) -> Vec<u8> {
     bincode::serialize(network).unwrap()
 }
 
-pub fn from_bytes(bytes: &[u8]) -> Network {
+/// The precision has to match the one the network was exported with
+pub fn from_bytes<T: Float + DeserializeOwned>(bytes: &[u8]) -> Network<T> {
     bincode::deserialize(bytes).unwrap()
 }
 
-pub fn to_file<S: AsRef<Path>>(path: S, network: &Network) {
-    write(path, to_bytes(&network)).unwrap();
+pub fn to_file<S: AsRef<Path>, T: Serialize>(path: S, network: &Network<T>) {
+    write(path, to_bytes(network)).unwrap();
 }
 
-pub fn from_file<S: AsRef<Path>>(path: S) -> Network {
+pub fn from_file<S: AsRef<Path>, T: Float + DeserializeOwned>(path: S) -> Network<T> {
     from_bytes(&read(path).unwrap())
 }
 
+/// Genomes keep everything needed to continue evolving them, unlike networks
+pub fn genome_to_bytes(genome: &Genome) -> Vec<u8> {
+    bincode::serialize(genome).unwrap()
+}
+
+pub fn genome_from_bytes(bytes: &[u8]) -> Genome {
+    bincode::deserialize(bytes).unwrap()
+}
+
+pub fn genome_to_file<S: AsRef<Path>>(path: S, genome: &Genome) {
+    write(path, genome_to_bytes(genome)).unwrap();
+}
+
+pub fn genome_from_file<S: AsRef<Path>>(path: S) -> Genome {
+    genome_from_bytes(&read(path).unwrap())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use neat_core::Genome;
 
     #[test]
     fn to_bytes_works() {
@@ -33,12 +53,27 @@ mod tests {
     #[test]
     fn from_bytes_works() {
         let mut network: Network = (&Genome::new(3, 1)).into();
-        let output_before = network.forward_pass(vec![1., 2., 3.]);
+        let output_before = network.forward_pass(&vec![1., 2., 3.]);
 
         let bytes = to_bytes(&network);
-        let mut imported_network = from_bytes(&bytes);
+        let mut imported_network: Network = from_bytes(&bytes);
 
-        let output_after = imported_network.forward_pass(vec![1., 2., 3.]);
+        let output_after = imported_network.forward_pass(&vec![1., 2., 3.]);
+
+        assert_eq!(output_before, output_after);
+    }
+
+    #[test]
+    fn f32_networks_are_smaller() {
+        let genome = Genome::new(3, 1);
+        let mut network: Network<f32> = (&genome).into();
+        let output_before = network.forward_pass(&vec![1., 2., 3.]);
+
+        let bytes = to_bytes(&network);
+        assert!(bytes.len() < to_bytes::<f64>(&(&genome).into()).len());
+
+        let mut imported_network: Network<f32> = from_bytes(&bytes);
+        let output_after = imported_network.forward_pass(&vec![1., 2., 3.]);
 
         assert_eq!(output_before, output_after);
     }
@@ -48,15 +83,32 @@ mod tests {
         let filename = "network.bin";
 
         let mut network: Network = (&Genome::new(3, 1)).into();
-        let output_before = network.forward_pass(vec![1., 2., 3.]);
+        let output_before = network.forward_pass(&vec![1., 2., 3.]);
 
         to_file(filename, &network);
-        let mut imported_network = from_file(filename);
+        let mut imported_network: Network = from_file(filename);
 
-        let output_after = imported_network.forward_pass(vec![1., 2., 3.]);
+        let output_after = imported_network.forward_pass(&vec![1., 2., 3.]);
 
         assert_eq!(output_before, output_after);
 
         std::fs::remove_file(filename).unwrap();
     }
+
+    #[test]
+    fn genome_file_import_export_works() {
+        let filename = "genome.bin";
+
+        let mut genome = Genome::new(3, 2);
+        genome.disable_connection(0);
+
+        genome_to_file(filename, &genome);
+        let imported_genome = genome_from_file(filename);
+
+        assert_eq!(imported_genome, genome);
+        assert_eq!(imported_genome.id(), genome.id());
+        assert!(imported_genome.connections()[0].disabled);
+
+        std::fs::remove_file(filename).unwrap();
+    }
 }